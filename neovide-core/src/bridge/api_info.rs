@@ -0,0 +1,113 @@
+use log::warn;
+use nvim_rs::UiAttachOptions;
+use rmpv::Value;
+
+use super::events::{parse_array, parse_map, parse_string, parse_u64, EventParseError};
+
+// Neovim 0.4 is the first release with api level 6, which is also when ext_linegrid landed
+const MINIMUM_API_LEVEL: u64 = 6;
+
+// Every extension neovim offers. Frontends only request the ones they draw themselves.
+pub const UI_EXTENSION_NAMES: &[&str] = &["multigrid", "cmdline", "popupmenu", "messages", "tabline", "hlstate"];
+
+#[derive(Debug, Clone)]
+pub struct ApiInformation {
+    pub channel: u64,
+    pub api_level: u64,
+    pub ui_options: Vec<String>
+}
+
+impl ApiInformation {
+    pub fn parse(api_info: &[Value]) -> Result<ApiInformation, EventParseError> {
+        if let [channel, metadata] = api_info {
+            let mut api_level = 0;
+            let mut ui_options = Vec::new();
+
+            for (key, value) in parse_map(metadata)? {
+                match parse_string(key)? {
+                    "version" => {
+                        for (version_key, version_value) in parse_map(value)? {
+                            if parse_string(version_key)? == "api_level" {
+                                api_level = parse_u64(version_value)?;
+                            }
+                        }
+                    },
+                    "ui_options" => {
+                        ui_options = parse_array(value)?
+                            .iter()
+                            .map(|option| parse_string(option).map(|option| option.to_string()))
+                            .collect::<Result<_, _>>()?;
+                    },
+                    _ => {}
+                }
+            }
+
+            Ok(ApiInformation { channel: parse_u64(channel)?, api_level, ui_options })
+        } else {
+            Err(EventParseError::InvalidEventFormat)
+        }
+    }
+
+    pub fn supports(&self, ui_option: &str) -> bool {
+        self.ui_options.iter().any(|supported| supported == ui_option)
+    }
+
+    pub fn is_compatible(&self) -> bool {
+        self.api_level >= MINIMUM_API_LEVEL && self.supports("ext_linegrid")
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiExtensions {
+    pub multigrid: bool,
+    pub cmdline: bool,
+    pub popupmenu: bool,
+    pub messages: bool,
+    pub tabline: bool,
+    pub hlstate: bool
+}
+
+impl UiExtensions {
+    pub fn from_names(names: &[String]) -> UiExtensions {
+        let requested = |name: &str| names.iter().any(|requested_name| requested_name == name);
+        UiExtensions {
+            multigrid: requested("multigrid"),
+            cmdline: requested("cmdline"),
+            popupmenu: requested("popupmenu"),
+            messages: requested("messages"),
+            tabline: requested("tabline"),
+            hlstate: requested("hlstate")
+        }
+    }
+
+    // Drops any requested extension the server doesn't know about so that older versions of
+    // neovim still attach, just without the extra features.
+    pub fn negotiate(&self, api_information: &ApiInformation) -> UiExtensions {
+        let enable = |requested: bool, name: &str| {
+            if requested && !api_information.supports(&format!("ext_{}", name)) {
+                warn!("Neovim does not support ext_{}, continuing without it", name);
+                false
+            } else {
+                requested
+            }
+        };
+
+        UiExtensions {
+            multigrid: enable(self.multigrid, "multigrid"),
+            cmdline: enable(self.cmdline, "cmdline"),
+            popupmenu: enable(self.popupmenu, "popupmenu"),
+            messages: enable(self.messages, "messages"),
+            tabline: enable(self.tabline, "tabline"),
+            hlstate: enable(self.hlstate, "hlstate")
+        }
+    }
+
+    pub fn apply(&self, options: &mut UiAttachOptions) {
+        options.set_multigrid_external(self.multigrid);
+        options.set_cmdline_external(self.cmdline);
+        options.set_popupmenu_external(self.popupmenu);
+        options.set_messages_external(self.messages);
+        options.set_tabline_external(self.tabline);
+        options.set_hlstate_external(self.hlstate);
+    }
+}
@@ -0,0 +1,36 @@
+use nvim_rs::Neovim;
+use nvim_rs::error::CallError;
+use rmpv::Value;
+
+use super::create::NeovimWriter;
+use super::requests::REQUEST_HANDLERS;
+
+fn version_part(part: &str) -> Value {
+    Value::from(part.parse::<u64>().unwrap_or(0))
+}
+
+fn methods() -> Vec<(Value, Value)> {
+    REQUEST_HANDLERS.iter().map(|(name, argument_count, _)| {
+        (Value::from(*name), Value::Map(vec![
+            (Value::from("async"), Value::from(false)),
+            (Value::from("nargs"), Value::from(*argument_count))
+        ]))
+    }).collect()
+}
+
+pub async fn set_client_info(nvim: &Neovim<NeovimWriter>, channel: u64) -> Result<(), Box<CallError>> {
+    let version = vec![
+        (Value::from("major"), version_part(env!("CARGO_PKG_VERSION_MAJOR"))),
+        (Value::from("minor"), version_part(env!("CARGO_PKG_VERSION_MINOR"))),
+        (Value::from("patch"), version_part(env!("CARGO_PKG_VERSION_PATCH")))
+    ];
+    let attributes = vec![
+        (Value::from("website"), Value::from("https://github.com/Kethku/neovide")),
+        (Value::from("license"), Value::from("MIT"))
+    ];
+
+    nvim.set_client_info("neovide", version, "ui", methods(), attributes).await?;
+    nvim.set_var("neovide_version", Value::from(env!("CARGO_PKG_VERSION"))).await?;
+    nvim.set_var("neovide_channel_id", Value::from(channel)).await?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::process::Stdio;
+
+use futures::AsyncWrite;
+use nvim_rs::{Neovim, compat::tokio::Compat, error::LoopError};
+use tokio::io::split;
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+#[cfg(windows)]
+use parity_tokio_ipc::Endpoint;
+use tokio::process::{Child, Command};
+use tokio::spawn;
+use tokio::task::JoinHandle;
+
+use super::handler::NeovimHandler;
+
+pub type NeovimWriter = Box<dyn AsyncWrite + Send + Unpin + 'static>;
+pub type NeovimIoHandle = JoinHandle<std::result::Result<(), Box<LoopError>>>;
+
+pub async fn new_child_cmd(command: &mut Command, handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle, Child)> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take()
+        .ok_or_else(|| Error::new(ErrorKind::Other, "Could not open neovim stdout"))?;
+    let stdin = child.stdin.take()
+        .ok_or_else(|| Error::new(ErrorKind::Other, "Could not open neovim stdin"))?;
+
+    let writer: NeovimWriter = Box::new(Compat::new(stdin));
+    let (neovim, io) = Neovim::<NeovimWriter>::new(Compat::new(stdout), writer, handler);
+    let io_handle = spawn(io);
+
+    Ok((neovim, io_handle, child))
+}
+
+pub async fn new_tcp(address: &str, handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle)> {
+    let stream = TcpStream::connect(address).await?;
+    let (reader, writer) = split(stream);
+
+    let writer: NeovimWriter = Box::new(Compat::new(writer));
+    let (neovim, io) = Neovim::<NeovimWriter>::new(Compat::new(reader), writer, handler);
+    let io_handle = spawn(io);
+
+    Ok((neovim, io_handle))
+}
+
+#[cfg(unix)]
+pub async fn new_socket(path: &Path, handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle)> {
+    let stream = UnixStream::connect(path).await?;
+    let (reader, writer) = split(stream);
+
+    let writer: NeovimWriter = Box::new(Compat::new(writer));
+    let (neovim, io) = Neovim::<NeovimWriter>::new(Compat::new(reader), writer, handler);
+    let io_handle = spawn(io);
+
+    Ok((neovim, io_handle))
+}
+
+// Neovim listens on a named pipe such as \\.\pipe\nvim-1234 on Windows
+#[cfg(windows)]
+pub async fn new_socket(path: &Path, handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle)> {
+    let stream = Endpoint::connect(path).await?;
+    let (reader, writer) = split(stream);
+
+    let writer: NeovimWriter = Box::new(Compat::new(writer));
+    let (neovim, io) = Neovim::<NeovimWriter>::new(Compat::new(reader), writer, handler);
+    let io_handle = spawn(io);
+
+    Ok((neovim, io_handle))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn new_socket(path: &Path, _handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle)> {
+    Err(Error::new(
+        ErrorKind::Other,
+        format!("Connecting to the socket {} is not supported on this platform", path.display())))
+}
@@ -2,9 +2,9 @@ use std::error;
 use std::fmt;
 
 use rmpv::Value;
-use skulpin::skia_safe::Color4f;
+use rmpv::decode::read_value;
 
-use crate::editor::{Colors, Style, CursorMode, CursorShape};
+use crate::editor::{Color, Colors, Style, CursorMode, CursorShape};
 
 #[derive(Debug, Clone)]
 pub enum EventParseError {
@@ -13,11 +13,13 @@ pub enum EventParseError {
     InvalidString(Value),
     InvalidU64(Value),
     InvalidI64(Value),
+    InvalidF64(Value),
     InvalidBool(Value),
+    InvalidWindowHandle(Value),
     InvalidWindowAnchor(Value),
     InvalidEventFormat
 }
-type Result<T> = std::result::Result<T, EventParseError>;
+pub(super) type Result<T> = std::result::Result<T, EventParseError>;
 
 impl fmt::Display for EventParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -27,7 +29,9 @@ impl fmt::Display for EventParseError {
             EventParseError::InvalidString(value) => write!(f, "invalid string format {}", value),
             EventParseError::InvalidU64(value) => write!(f, "invalid u64 format {}", value),
             EventParseError::InvalidI64(value) => write!(f, "invalid i64 format {}", value),
+            EventParseError::InvalidF64(value) => write!(f, "invalid f64 format {}", value),
             EventParseError::InvalidBool(value) => write!(f, "invalid bool format {}", value),
+            EventParseError::InvalidWindowHandle(value) => write!(f, "invalid window handle format {}", value),
             EventParseError::InvalidWindowAnchor(value) => write!(f, "invalid window anchor format {}", value),
             EventParseError::InvalidEventFormat => write!(f, "invalid event format")
         }
@@ -125,9 +129,10 @@ pub enum RedrawEvent {
     Clear { grid: u64 },
     CursorGoto { grid: u64, row: u64, column: u64 },
     Scroll { grid: u64, top: u64, bottom: u64, left: u64, right: u64, rows: i64, columns: i64 },
-    WindowPosition { grid: u64, window: u64, start_row: u64, start_column: u64, width: u64, height: u64 },
-    WindowFloatPosition { grid: u64, window: u64, anchor: WindowAnchor, anchor_grid: u64, anchor_row: u64, anchor_column: u64, focusable: bool },
-    WindowExternalPosition { grid: u64, window: u64 },
+    Destroy { grid: u64 },
+    WindowPosition { grid: u64, window: i64, start_row: u64, start_column: u64, width: u64, height: u64 },
+    WindowFloatPosition { grid: u64, window: i64, anchor: WindowAnchor, anchor_grid: u64, anchor_row: f64, anchor_column: f64, focusable: bool },
+    WindowExternalPosition { grid: u64, window: i64 },
     WindowHide { grid: u64 },
     WindowClose { grid: u64 },
     MessageSetPosition { grid: u64, row: u64, scrolled: bool, separator_character: String },
@@ -146,12 +151,21 @@ pub enum RedrawEvent {
     MessageHistoryShow { entries: Vec<(MessageKind, StyledContent)>}
 }
 
-fn unpack_color(packed_color: u64) -> Color4f {
+impl RedrawEvent {
+    pub fn is_flush(&self) -> bool {
+        match self {
+            RedrawEvent::Flush => true,
+            _ => false
+        }
+    }
+}
+
+fn unpack_color(packed_color: u64) -> Color {
     let packed_color = packed_color as u32;
     let r = ((packed_color & 0xff0000) >> 16) as f32;
     let g = ((packed_color & 0xff00) >> 8) as f32;
     let b = (packed_color & 0xff) as f32;
-    Color4f {
+    Color {
         r: r / 255.0,
         g: g / 255.0,
         b: b / 255.0,
@@ -159,7 +173,7 @@ fn unpack_color(packed_color: u64) -> Color4f {
     }
 }
 
-fn parse_array(array_value: &Value) -> Result<&[Value]> {
+pub(super) fn parse_array(array_value: &Value) -> Result<&[Value]> {
     if let Value::Array(content) = array_value {
         Ok(&content[..])
     } else {
@@ -167,7 +181,7 @@ fn parse_array(array_value: &Value) -> Result<&[Value]> {
     }
 }
 
-fn parse_map(map_value: &Value) -> Result<&[(Value, Value)]> {
+pub(super) fn parse_map(map_value: &Value) -> Result<&[(Value, Value)]> {
     if let Value::Map(content) = map_value {
         Ok(&content[..])
     } else {
@@ -175,7 +189,7 @@ fn parse_map(map_value: &Value) -> Result<&[(Value, Value)]> {
     }
 }
 
-fn parse_string(string_value: &Value) -> Result<&str> {
+pub(super) fn parse_string(string_value: &Value) -> Result<&str> {
     if let Value::String(content) = string_value {
         content.as_str().ok_or_else(|| EventParseError::InvalidString(string_value.clone()))
     } else {
@@ -183,7 +197,7 @@ fn parse_string(string_value: &Value) -> Result<&str> {
     }
 }
 
-fn parse_u64(u64_value: &Value) -> Result<u64> {
+pub(super) fn parse_u64(u64_value: &Value) -> Result<u64> {
     if let Value::Integer(content) = u64_value {
         Ok(content.as_u64().ok_or_else(|| EventParseError::InvalidU64(u64_value.clone()))?)
     } else {
@@ -191,7 +205,7 @@ fn parse_u64(u64_value: &Value) -> Result<u64> {
     }
 }
 
-fn parse_i64(i64_value: &Value) -> Result<i64> {
+pub(super) fn parse_i64(i64_value: &Value) -> Result<i64> {
     if let Value::Integer(content) = i64_value {
         Ok(content.as_i64().ok_or_else(|| EventParseError::InvalidI64(i64_value.clone()))?)
     } else {
@@ -199,7 +213,30 @@ fn parse_i64(i64_value: &Value) -> Result<i64> {
     }
 }
 
-fn parse_bool(bool_value: &Value) -> Result<bool> {
+// Neovim sends whole numbers as integers even where a float is expected
+pub(super) fn parse_f64(f64_value: &Value) -> Result<f64> {
+    match f64_value {
+        Value::F64(content) => Ok(*content),
+        Value::F32(content) => Ok(*content as f64),
+        Value::Integer(content) => content.as_f64().ok_or_else(|| EventParseError::InvalidF64(f64_value.clone())),
+        _ => Err(EventParseError::InvalidF64(f64_value.clone()))
+    }
+}
+
+// Window handles arrive as msgpack extension values wrapping the handle as an encoded integer.
+// The popup menu is placed like a float but has no window, which neovim sends as -1.
+fn parse_window_handle(window_value: &Value) -> Result<i64> {
+    match window_value {
+        Value::Ext(_, data) => read_value(&mut &data[..])
+            .ok()
+            .and_then(|handle| handle.as_i64())
+            .ok_or_else(|| EventParseError::InvalidWindowHandle(window_value.clone())),
+        Value::Integer(_) => parse_i64(window_value),
+        _ => Err(EventParseError::InvalidWindowHandle(window_value.clone()))
+    }
+}
+
+pub(super) fn parse_bool(bool_value: &Value) -> Result<bool> {
     if let Value::Boolean(content) = bool_value {
         Ok(*content)
     } else {
@@ -419,7 +456,7 @@ fn parse_win_pos(win_pos_arguments: &[Value]) -> Result<RedrawEvent> {
     if let [grid, window, start_row, start_column, width, height] = win_pos_arguments {
         Ok(RedrawEvent::WindowPosition {
             grid: parse_u64(grid)?,
-            window: parse_u64(window)?,
+            window: parse_window_handle(window)?,
             start_row: parse_u64(start_row)?,
             start_column: parse_u64(start_column)?,
             width: parse_u64(width)?,
@@ -444,11 +481,11 @@ fn parse_win_float_pos(win_float_pos_arguments: &[Value]) -> Result<RedrawEvent>
     if let [grid, window, anchor, anchor_grid, anchor_row, anchor_column, focusable] = win_float_pos_arguments {
         Ok(RedrawEvent::WindowFloatPosition {
             grid: parse_u64(grid)?,
-            window: parse_u64(window)?,
+            window: parse_window_handle(window)?,
             anchor: parse_window_anchor(anchor)?,
             anchor_grid: parse_u64(anchor_grid)?,
-            anchor_row: parse_u64(anchor_row)?,
-            anchor_column: parse_u64(anchor_column)?,
+            anchor_row: parse_f64(anchor_row)?,
+            anchor_column: parse_f64(anchor_column)?,
             focusable: parse_bool(focusable)?
         })
     } else {
@@ -460,7 +497,7 @@ fn parse_win_external_pos(win_external_pos_arguments: &[Value]) -> Result<Redraw
     if let [grid, window] = win_external_pos_arguments {
         Ok(RedrawEvent::WindowExternalPosition {
             grid: parse_u64(grid)?,
-            window: parse_u64(window)?
+            window: parse_window_handle(window)?
         })
     } else {
         Err(EventParseError::InvalidEventFormat)
@@ -487,6 +524,16 @@ fn parse_win_close(win_close_arguments: &[Value]) -> Result<RedrawEvent> {
     }
 }
 
+fn parse_grid_destroy(grid_destroy_arguments: &[Value]) -> Result<RedrawEvent> {
+    if let [grid] = grid_destroy_arguments {
+        Ok(RedrawEvent::Destroy {
+            grid: parse_u64(grid)?
+        })
+    } else {
+        Err(EventParseError::InvalidEventFormat)
+    }
+}
+
 fn parse_msg_set_pos(msg_set_pos_arguments: &[Value]) -> Result<RedrawEvent> {
     if let [grid, row, scrolled, separator_character] = msg_set_pos_arguments {
         Ok(RedrawEvent::MessageSetPosition {
@@ -662,6 +709,7 @@ pub fn parse_redraw_event(event_value: &Value) -> Result<Vec<RedrawEvent>> {
             "grid_clear" => Some(parse_clear(event_parameters)?),
             "grid_cursor_goto" => Some(parse_cursor_goto(event_parameters)?),
             "grid_scroll" => Some(parse_grid_scroll(event_parameters)?),
+            "grid_destroy" => Some(parse_grid_destroy(event_parameters)?),
             "win_pos" => Some(parse_win_pos(event_parameters)?),
             "win_float_pos" => Some(parse_win_float_pos(event_parameters)?),
             "win_external_pos" => Some(parse_win_external_pos(event_parameters)?),
@@ -692,7 +740,7 @@ pub fn parse_redraw_event(event_value: &Value) -> Result<Vec<RedrawEvent>> {
     Ok(parsed_events)
 }
 
-pub(in super) fn parse_neovim_event(event_name: &str, arguments: &[Value]) -> Result<Vec<RedrawEvent>> {
+pub fn parse_neovim_event(event_name: &str, arguments: &[Value]) -> Result<Vec<RedrawEvent>> {
     let mut resulting_events = Vec::with_capacity(arguments.len());
     if event_name == "redraw" {
         for event in arguments {
@@ -709,3 +757,60 @@ pub(in super) fn parse_neovim_event(event_name: &str, arguments: &[Value]) -> Re
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use rmpv::encode::write_value;
+
+    use super::*;
+
+    // Neovim sends window handles as extension values of type 1 which wrap the encoded handle
+    fn window_ext(handle: i64) -> Value {
+        let mut data = Vec::new();
+        write_value(&mut data, &Value::from(handle)).unwrap();
+        Value::Ext(1, data)
+    }
+
+    #[test]
+    fn window_handles_parse_from_ext_and_integer_values() {
+        assert_eq!(parse_window_handle(&window_ext(1000)).unwrap(), 1000);
+        assert_eq!(parse_window_handle(&Value::from(1001)).unwrap(), 1001);
+        assert!(parse_window_handle(&Value::from("1000")).is_err());
+        // 0xc1 is never valid msgpack
+        assert!(parse_window_handle(&Value::Ext(1, vec![0xc1])).is_err());
+    }
+
+    #[test]
+    fn win_pos_reads_the_window_from_an_ext_value() {
+        let arguments = [Value::from(2), window_ext(1000), Value::from(1), Value::from(3), Value::from(40), Value::from(10)];
+        match parse_win_pos(&arguments) {
+            Ok(RedrawEvent::WindowPosition { grid: 2, window: 1000, start_row: 1, start_column: 3, width: 40, height: 10 }) => {},
+            other => panic!("unexpected win_pos {:?}", other)
+        }
+    }
+
+    // The popup menu has no window, which neovim sends as a plain -1
+    #[test]
+    fn popup_menu_window_handle_is_negative() {
+        assert_eq!(parse_window_handle(&Value::from(-1)).unwrap(), -1);
+        assert_eq!(parse_window_handle(&window_ext(-1)).unwrap(), -1);
+    }
+
+    #[test]
+    fn win_float_pos_keeps_fractional_anchors() {
+        let arguments = [
+            Value::from(4), Value::from(-1), Value::from("SE"), Value::from(2),
+            Value::F64(1.5), Value::from(3), Value::Boolean(false)
+        ];
+        match parse_win_float_pos(&arguments) {
+            Ok(RedrawEvent::WindowFloatPosition {
+                grid: 4, window: -1, anchor: WindowAnchor::SouthEast, anchor_grid: 2,
+                anchor_row, anchor_column, focusable: false
+            }) => {
+                assert_eq!(anchor_row, 1.5);
+                assert_eq!(anchor_column, 3.0);
+            },
+            other => panic!("unexpected win_float_pos {:?}", other)
+        }
+    }
+}
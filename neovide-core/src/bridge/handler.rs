@@ -0,0 +1,98 @@
+use log::error;
+use rmpv::Value;
+use nvim_rs::{Neovim, Handler};
+use async_trait::async_trait;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::mpsc::error::SendError;
+
+use crate::session::SessionContext;
+use super::create::NeovimWriter;
+use super::events::{RedrawEvent, parse_neovim_event};
+use super::notifications::{handle_notification, is_neovide_notification};
+use super::recording::record_notification;
+use super::requests::handle_request;
+
+#[derive(Clone)]
+pub struct NeovimHandler {
+    sender: UnboundedSender<RedrawEvent>,
+    context: SessionContext
+}
+
+impl NeovimHandler {
+    pub fn new(context: SessionContext) -> NeovimHandler {
+        let (sender, mut receiver) = unbounded_channel::<RedrawEvent>();
+
+        let batch_context = context.clone();
+        tokio::spawn(async move {
+            // Events are staged until neovim flushes so that the renderer never observes a
+            // partially applied batch.
+            let mut staged_events = Vec::new();
+            while let Some(event) = receiver.recv().await {
+                let flush = event.is_flush();
+                staged_events.push(event);
+
+                if flush {
+                    let mut editor = batch_context.editor.lock().unwrap();
+                    for event in staged_events.drain(..) {
+                        editor.handle_redraw_event(event);
+                    }
+                    batch_context.redraw_scheduler.queue_next_frame();
+                }
+            }
+        });
+
+        NeovimHandler {
+            sender,
+            context
+        }
+    }
+
+    // Fails once the task which applies events to the editor has stopped
+    pub fn handle_redraw_event(&self, event: RedrawEvent) -> Result<(), SendError<RedrawEvent>> {
+        self.sender.send(event)
+    }
+}
+
+
+#[async_trait]
+impl Handler for NeovimHandler {
+    type Writer = NeovimWriter;
+
+    async fn handle_notify(&self, event_name: String, arguments: Vec<Value>, neovim: Neovim<NeovimWriter>) {
+        record_notification(&self.context, &event_name, &arguments);
+
+        if is_neovide_notification(&event_name) {
+            if let Err(error) = handle_notification(&self.context, &event_name, &arguments) {
+                // Notifications can't be answered, so tell the user directly instead. The reply
+                // is read by the IO loop which is running this handler, so waiting for it here
+                // would never finish.
+                let message = format!("Neovide: {}", error);
+                tokio::spawn(async move {
+                    if let Err(write_error) = neovim.err_writeln(&message).await {
+                        error!("Could not report {} to neovim: {}", message, write_error);
+                    }
+                });
+            }
+            return;
+        }
+
+        let parsed_events = match parse_neovim_event(&event_name, &arguments) {
+            Ok(parsed_events) => parsed_events,
+            Err(error) => {
+                error!("Could not parse event from neovim: {}", error);
+                return;
+            }
+        };
+        for event in parsed_events {
+            if let Err(error) = self.handle_redraw_event(event) {
+                error!("Could not process neovim event: {}", error);
+                return;
+            }
+        }
+    }
+
+    async fn handle_request(&self, method: String, arguments: Vec<Value>, _neovim: Neovim<NeovimWriter>) -> Result<Value, Value> {
+        handle_request(&self.context, &method, &arguments)
+            .map_err(|error| Value::from(error.to_string()))
+    }
+}
@@ -0,0 +1,517 @@
+mod api_info;
+mod client_info;
+mod create;
+pub mod events;
+mod handler;
+mod notifications;
+mod recording;
+mod requests;
+mod ui_commands;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use log::{error, warn};
+use rmpv::Value;
+use nvim_rs::{Neovim, UiAttachOptions, error::LoopError};
+use tokio::runtime::Runtime;
+use tokio::process::{Child, Command};
+use tokio::task::JoinError;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::error::SendError;
+
+pub use events::*;
+pub use api_info::{UiExtensions, UI_EXTENSION_NAMES};
+pub use ui_commands::UiCommand;
+pub use recording::apply_recording;
+pub use recording::Recorder;
+use api_info::ApiInformation;
+use ui_commands::coalesce_resizes;
+use crate::editor::Editor;
+use crate::session::{SessionContext, SessionOptions};
+use create::{NeovimWriter, NeovimIoHandle};
+use handler::NeovimHandler;
+
+#[derive(Debug, Clone)]
+pub enum Connection {
+    Embedded(Vec<String>),
+    Tcp(String),
+    Socket(PathBuf),
+    Replay { path: PathBuf, realtime: bool }
+}
+
+// A host:port address has a non empty host and a numeric port after the last colon. Windows
+// paths such as C:\nvim.sock have a colon too, but never a port after it.
+fn is_tcp_address(address: &str) -> bool {
+    let mut parts = address.rsplitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(port), Some(host)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+        _ => false
+    }
+}
+
+impl Connection {
+    // Anything which isn't host:port is a unix socket or, on Windows, a named pipe path
+    pub fn from_address(address: String) -> Connection {
+        if is_tcp_address(&address) {
+            Connection::Tcp(address)
+        } else {
+            Connection::Socket(PathBuf::from(address))
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        match self {
+            Connection::Tcp(_) | Connection::Socket(_) => true,
+            _ => false
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn set_windows_creation_flags(cmd: &mut Command) {
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+}
+
+fn create_nvim_command(arguments: &[String], options: &SessionOptions) -> Command {
+    let mut cmd = match options.wrapper.split_first() {
+        Some((program, wrapper_arguments)) => {
+            let mut cmd = Command::new(program);
+            cmd.args(wrapper_arguments).arg(&options.neovim_bin);
+            cmd
+        },
+        None => Command::new(&options.neovim_bin)
+    };
+
+    cmd.arg("--embed")
+        .args(arguments)
+        .stderr(Stdio::inherit());
+
+    #[cfg(target_os = "windows")]
+    set_windows_creation_flags(&mut cmd);
+
+    cmd
+}
+
+fn describe_spawn_error(error: io::Error, options: &SessionOptions) -> String {
+    let program = options.wrapper.first().unwrap_or(&options.neovim_bin);
+    if error.kind() == io::ErrorKind::NotFound {
+        format!(
+            "Could not find `{}`. Make sure neovim is installed and on your PATH, or point Neovide at it with --neovim-bin or the NEOVIM_BIN environment variable.",
+            program)
+    } else {
+        format!("Could not start `{}`: {}", program, error)
+    }
+}
+
+async fn drain(receiver: &mut UnboundedReceiver<UiCommand>) -> Option<Vec<UiCommand>> {
+    if let Some(ui_command) = receiver.recv().await {
+        let mut results = vec![ui_command];
+        while let Ok(ui_command) = receiver.try_recv() {
+            results.push(ui_command);
+        }
+        Some(results)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionStatus {
+    Starting,
+    Running,
+    FailedToStart(String),
+    Crashed(String),
+    Disconnected(String),
+    // Neovim quit or the ui detached from it. It is up to the frontend to close.
+    Exited
+}
+
+impl SessionStatus {
+    pub fn is_running(&self) -> bool {
+        match self {
+            SessionStatus::Running => true,
+            _ => false
+        }
+    }
+
+    pub fn has_ended(&self) -> bool {
+        match self {
+            SessionStatus::FailedToStart(_) | SessionStatus::Crashed(_) | SessionStatus::Disconnected(_) => true,
+            _ => false
+        }
+    }
+
+    pub fn has_exited(&self) -> bool {
+        match self {
+            SessionStatus::Exited => true,
+            _ => false
+        }
+    }
+}
+
+enum SessionExit {
+    Quit,
+    FailedToStart(String),
+    Crashed(String),
+    Disconnected(String),
+    Closed
+}
+
+// Sends a batch of queued commands to neovim, keeping only the last resize of the batch.
+// Returns true once the ui has detached, in which case the rest of the batch is dropped.
+async fn execute_commands(nvim: &Neovim<NeovimWriter>, commands: Vec<UiCommand>) -> bool {
+    for command in coalesce_resizes(commands) {
+        let detaching = command.is_detach();
+        let description = format!("{:?}", command);
+        if let Err(error) = command.execute(nvim).await {
+            error!("Could not send {} to neovim: {}", description, error);
+        }
+        if detaching {
+            return true;
+        }
+    }
+    false
+}
+
+async fn connect(connection: &Connection, options: &SessionOptions, handler: NeovimHandler) -> Result<(Neovim<NeovimWriter>, NeovimIoHandle, Option<Child>), String> {
+    match connection {
+        Connection::Embedded(arguments) => create::new_child_cmd(&mut create_nvim_command(arguments, options), handler).await
+            .map(|(nvim, io_handler, child)| (nvim, io_handler, Some(child)))
+            .map_err(|error| describe_spawn_error(error, options)),
+        Connection::Tcp(address) => create::new_tcp(address, handler).await
+            .map(|(nvim, io_handler)| (nvim, io_handler, None))
+            .map_err(|error| format!("Could not connect to the neovim server at {}: {}", address, error)),
+        Connection::Socket(path) => create::new_socket(path, handler).await
+            .map(|(nvim, io_handler)| (nvim, io_handler, None))
+            .map_err(|error| format!("Could not connect to the neovim socket at {}: {}", path.display(), error)),
+        Connection::Replay { path, .. } => Err(format!("The recording {} is played back rather than connected to", path.display()))
+    }
+}
+
+async fn attach(nvim: &Neovim<NeovimWriter>, context: &SessionContext) -> Result<UiExtensions, String> {
+    let (width, height) = context.editor.lock().unwrap().size;
+
+    let api_info = nvim.get_api_info().await
+        .map_err(|error| format!("Could not query the neovim api: {}", error))?;
+    let api_information = ApiInformation::parse(&api_info)
+        .map_err(|error| format!("Could not parse the neovim api information: {}", error))?;
+    if !api_information.is_compatible() {
+        return Err("Neovide requires version 0.4 or higher".to_string());
+    }
+
+    let requested_extensions = UiExtensions::from_names(&context.options.ui_extensions);
+    let ui_extensions = requested_extensions.negotiate(&api_information);
+
+    nvim.set_var("neovide", Value::Boolean(true)).await
+        .map_err(|error| format!("Could not communicate with neovim process: {}", error))?;
+    client_info::set_client_info(nvim, api_information.channel).await
+        .map_err(|error| format!("Could not set client info: {}", error))?;
+    notifications::load_settings(nvim, context).await
+        .map_err(|error| format!("Could not read the neovide settings: {}", error))?;
+    nvim.command(&notifications::setting_watcher_command()).await
+        .map_err(|error| format!("Could not watch the neovide settings: {}", error))?;
+    if context.options.clipboard.is_enabled() {
+        nvim.command(&requests::clipboard_provider_command()).await
+            .map_err(|error| format!("Could not register the clipboard provider: {}", error))?;
+    }
+    let mut options = UiAttachOptions::new();
+    options.set_linegrid_external(true);
+    options.set_rgb(true);
+    ui_extensions.apply(&mut options);
+    nvim.ui_attach(width as i64, height as i64, &options).await
+        .map_err(|error| format!("Could not attach ui to neovim process: {}", error))?;
+    Ok(ui_extensions)
+}
+
+async fn session_exit(io_result: Result<Result<(), Box<LoopError>>, JoinError>, child: Option<Child>) -> SessionExit {
+    let error_message = match io_result {
+        Err(join_error) => Some(format!("Error joining IO loop: {}", join_error)),
+        Ok(Err(error)) if !error.is_channel_closed() => Some(error.to_string()),
+        _ => None
+    };
+
+    match child {
+        Some(mut child) => {
+            if error_message.is_some() {
+                // The process may still be alive if only the IO loop failed
+                child.kill().ok();
+            }
+            match child.await {
+                Ok(exit_status) if exit_status.success() && error_message.is_none() => SessionExit::Quit,
+                Ok(exit_status) => SessionExit::Crashed(
+                    error_message.unwrap_or_else(|| format!("Neovim exited with {}", exit_status))),
+                Err(error) => SessionExit::Crashed(error_message.unwrap_or_else(|| error.to_string()))
+            }
+        },
+        None => SessionExit::Disconnected(
+            error_message.unwrap_or_else(|| "The neovim server closed the connection".to_string()))
+    }
+}
+
+// Plays a recording without any neovim process. Input has nowhere to go, so ui commands are
+// dropped until the window closes.
+async fn run_replay(path: &Path, realtime: bool, receiver: &mut UnboundedReceiver<UiCommand>, context: &SessionContext) -> SessionExit {
+    context.set_status(SessionStatus::Running);
+    if let Err(message) = recording::replay(path, realtime, context).await {
+        return SessionExit::Crashed(message);
+    }
+    while receiver.recv().await.is_some() {}
+    SessionExit::Closed
+}
+
+async fn run_session(connection: &Connection, receiver: &mut UnboundedReceiver<UiCommand>, context: &SessionContext) -> SessionExit {
+    if let Connection::Replay { path, realtime } = connection {
+        return run_replay(path, *realtime, receiver, context).await;
+    }
+
+    let failed = |message| if connection.is_remote() {
+        SessionExit::Disconnected(message)
+    } else {
+        SessionExit::FailedToStart(message)
+    };
+
+    let (nvim, mut io_handler, child) = match connect(connection, &context.options, NeovimHandler::new(context.clone())).await {
+        Ok(session) => session,
+        Err(message) => return failed(message)
+    };
+
+    // A restart or reconnect starts from an empty screen rather than the grids and floats the
+    // previous session left behind
+    {
+        let mut editor = context.editor.lock().unwrap();
+        *editor = Editor::new(editor.size);
+    }
+
+    match attach(&nvim, context).await {
+        Ok(ui_extensions) => *context.ui_extensions.lock().unwrap() = ui_extensions,
+        Err(message) => return failed(message)
+    }
+    context.set_status(SessionStatus::Running);
+
+    loop {
+        tokio::select! {
+            io_result = &mut io_handler => return session_exit(io_result, child).await,
+            commands = drain(receiver) => {
+                match commands {
+                    Some(commands) if !execute_commands(&nvim, commands).await => {},
+                    _ => return SessionExit::Closed
+                }
+            }
+        }
+    }
+}
+
+async fn wait_for_restart(receiver: &mut UnboundedReceiver<UiCommand>) -> bool {
+    while let Some(command) = receiver.recv().await {
+        if let UiCommand::Restart = command {
+            return true;
+        }
+    }
+    false
+}
+
+async fn start_process(connection: Connection, mut receiver: UnboundedReceiver<UiCommand>, context: SessionContext) {
+    loop {
+        context.set_status(SessionStatus::Starting);
+
+        match run_session(&connection, &mut receiver, &context).await {
+            SessionExit::Quit | SessionExit::Closed => {
+                context.set_status(SessionStatus::Exited);
+                return;
+            },
+            SessionExit::FailedToStart(message) => {
+                error!("Could not start neovim: {}", message);
+                context.set_status(SessionStatus::FailedToStart(message));
+            },
+            SessionExit::Crashed(message) => {
+                error!("Neovim session ended: {}", message);
+                context.set_status(SessionStatus::Crashed(message));
+            },
+            SessionExit::Disconnected(message) => {
+                warn!("Neovim connection lost: {}", message);
+                context.set_status(SessionStatus::Disconnected(message));
+            }
+        }
+
+        if !wait_for_restart(&mut receiver).await {
+            break;
+        }
+    }
+}
+
+pub struct Bridge {
+    _runtime: Runtime,
+    sender: UnboundedSender<UiCommand>,
+    pub connection: Connection
+}
+
+impl Bridge {
+    pub fn new(connection: Connection, context: SessionContext) -> Bridge {
+        let runtime = Runtime::new().unwrap();
+        let (sender, receiver) = unbounded_channel::<UiCommand>();
+
+        let process_connection = connection.clone();
+        runtime.spawn(async move {
+            start_process(process_connection, receiver, context).await;
+        });
+
+        Bridge { _runtime: runtime, sender, connection }
+    }
+
+    // Fails once the session's runtime has shut down and nothing is left to run the command
+    pub fn queue_command(&self, command: UiCommand) -> Result<(), SendError<UiCommand>> {
+        self.sender.send(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+    use crate::clipboard::ClipboardSetting;
+
+    fn is_tcp(address: &str) -> bool {
+        match Connection::from_address(address.to_string()) {
+            Connection::Tcp(_) => true,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn host_and_port_connect_over_tcp() {
+        assert!(is_tcp("127.0.0.1:6666"));
+        assert!(is_tcp("localhost:6666"));
+        assert!(is_tcp("[::1]:6666"));
+    }
+
+    #[test]
+    fn everything_else_is_a_socket_path() {
+        assert!(!is_tcp("nvim.sock"));
+        assert!(!is_tcp("/tmp/nvim.sock"));
+        assert!(!is_tcp(":6666"));
+        assert!(!is_tcp("localhost:nvim"));
+        assert!(!is_tcp("C:\\Users\\me\\nvim.sock"));
+        assert!(!is_tcp("\\\\.\\pipe\\nvim-1234"));
+    }
+
+    // Stands in for neovim at the other end of a socket pair. Every request is recorded as its
+    // method name and arguments, in the order it arrived, and answered straight away.
+    #[cfg(unix)]
+    fn mock_neovim(context: &SessionContext) -> (Neovim<NeovimWriter>, Arc<Mutex<Vec<(String, Vec<Value>)>>>) {
+        use std::os::unix::net::UnixStream;
+        use nvim_rs::compat::tokio::Compat;
+        use rmpv::decode::read_value;
+        use rmpv::encode::write_value;
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded_requests = requests.clone();
+        thread::spawn(move || {
+            let mut reader = server.try_clone().unwrap();
+            let mut writer = server;
+            while let Ok(Value::Array(message)) = read_value(&mut reader) {
+                if let [_, id, method, Value::Array(arguments)] = &message[..] {
+                    let method = method.as_str().unwrap_or_default().to_string();
+                    // nvim_paste returns false to cancel the paste
+                    let result = if method == "nvim_paste" { Value::Boolean(true) } else { Value::Nil };
+                    recorded_requests.lock().unwrap().push((method, arguments.clone()));
+                    let response = Value::Array(vec![Value::from(1), id.clone(), Value::Nil, result]);
+                    if write_value(&mut writer, &response).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+
+        client.set_nonblocking(true).unwrap();
+        let stream = tokio::net::UnixStream::from_std(client).unwrap();
+        let (reader, writer) = tokio::io::split(stream);
+        let writer: NeovimWriter = Box::new(Compat::new(writer));
+        let (nvim, io) = Neovim::<NeovimWriter>::new(Compat::new(reader), writer, NeovimHandler::new(context.clone()));
+        tokio::spawn(io);
+        (nvim, requests)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn queued_commands_reach_neovim_in_order_with_only_the_last_resize() {
+        let context = SessionContext::new(SessionOptions::default());
+        let (nvim, requests) = mock_neovim(&context);
+
+        let (sender, mut receiver) = unbounded_channel();
+        sender.send(UiCommand::Resize { width: 80, height: 24 }).unwrap();
+        sender.send(UiCommand::Keyboard("a".to_string())).unwrap();
+        sender.send(UiCommand::Resize { width: 120, height: 40 }).unwrap();
+        sender.send(UiCommand::Drag { grid: 0, position: (2, 3) }).unwrap();
+        sender.send(UiCommand::Keyboard("b".to_string())).unwrap();
+
+        let commands = drain(&mut receiver).await.unwrap();
+        assert!(!execute_commands(&nvim, commands).await);
+
+        let requests = requests.lock().unwrap();
+        let methods: Vec<&str> = requests.iter().map(|(method, _)| method.as_str()).collect();
+        assert_eq!(methods, vec!["nvim_input", "nvim_ui_try_resize", "nvim_input_mouse", "nvim_input"]);
+        assert_eq!(requests[0].1, vec![Value::from("a")]);
+        assert_eq!(requests[1].1, vec![Value::from(120), Value::from(40)]);
+        assert_eq!(requests[3].1, vec![Value::from("b")]);
+    }
+
+    // How neovim's own clipboard provider reacts can only be seen in a real neovim, so this is
+    // skipped when there is no nvim on the PATH. The child gets an empty PATH so that it finds
+    // no clipboard tool and has('clipboard') starts out false.
+    #[tokio::test]
+    async fn clipboard_provider_takes_over_when_neovim_has_no_clipboard_tool() {
+        let neovim_bin = env::var_os("PATH")
+            .and_then(|paths| env::split_paths(&paths)
+                .map(|directory| directory.join("nvim"))
+                .find(|path| path.is_file()));
+        let neovim_bin = match neovim_bin {
+            Some(neovim_bin) => neovim_bin.to_string_lossy().into_owned(),
+            None => {
+                eprintln!("nvim is not installed, skipping");
+                return;
+            }
+        };
+
+        let options = SessionOptions { neovim_bin, clipboard: ClipboardSetting::Memory, ..SessionOptions::default() };
+        let context = SessionContext::new(options);
+        let mut command = create_nvim_command(&["--clean".to_string(), "--headless".to_string()], &context.options);
+        command.env("PATH", "");
+        let (nvim, _io_handler, mut child) = create::new_child_cmd(&mut command, NeovimHandler::new(context.clone())).await.unwrap();
+
+        assert_eq!(nvim.eval("has('clipboard')").await.unwrap(), Value::from(0));
+        nvim.command(&requests::clipboard_provider_command()).await.unwrap();
+        assert_eq!(nvim.eval("has('clipboard')").await.unwrap(), Value::from(1));
+        assert_eq!(nvim.eval("provider#clipboard#Executable()").await.unwrap(), Value::from("neovide"));
+
+        // A clipboard the user set up is left alone
+        nvim.command("let g:clipboard.name = 'mine'").await.unwrap();
+        nvim.command(&requests::clipboard_provider_command()).await.unwrap();
+        assert_eq!(nvim.eval("g:clipboard.name").await.unwrap(), Value::from("mine"));
+
+        child.kill().ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn detaching_ends_the_session_without_sending_the_rest() {
+        let context = SessionContext::new(SessionOptions::default());
+        let (nvim, requests) = mock_neovim(&context);
+
+        let commands = vec![
+            UiCommand::Keyboard("a".to_string()),
+            UiCommand::Detach,
+            UiCommand::Keyboard("b".to_string())
+        ];
+        assert!(execute_commands(&nvim, commands).await);
+
+        let requests = requests.lock().unwrap();
+        let methods: Vec<&str> = requests.iter().map(|(method, _)| method.as_str()).collect();
+        assert_eq!(methods, vec!["nvim_input", "nvim_ui_detach"]);
+    }
+}
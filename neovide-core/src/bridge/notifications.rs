@@ -0,0 +1,237 @@
+use std::fmt;
+
+use nvim_rs::Neovim;
+use nvim_rs::error::CallError;
+use rmpv::Value;
+
+use crate::session::SessionContext;
+use crate::settings::{SettingError, Settings, SETTING_NAMES};
+use super::create::NeovimWriter;
+use super::events::{parse_map, parse_string, EventParseError};
+
+const SETTING_VARIABLE_PREFIX: &str = "neovide_";
+
+#[derive(Debug, Clone)]
+pub enum NotificationError {
+    UnknownNotification(String),
+    InvalidArguments(EventParseError),
+    InvalidSetting(SettingError)
+}
+type Result<T> = std::result::Result<T, NotificationError>;
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotificationError::UnknownNotification(name) => write!(f, "unknown neovide notification {}", name),
+            NotificationError::InvalidArguments(error) => write!(f, "invalid arguments: {}", error),
+            NotificationError::InvalidSetting(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl From<EventParseError> for NotificationError {
+    fn from(error: EventParseError) -> NotificationError {
+        NotificationError::InvalidArguments(error)
+    }
+}
+
+impl From<SettingError> for NotificationError {
+    fn from(error: SettingError) -> NotificationError {
+        NotificationError::InvalidSetting(error)
+    }
+}
+
+type NotificationHandler = fn(&SessionContext, &[Value]) -> Result<()>;
+
+// Every notification neovide listens for, as (notification name, handler)
+pub const NOTIFICATION_HANDLERS: &[(&str, NotificationHandler)] = &[
+    ("neovide.set", set_settings),
+    ("neovide.setting_changed", setting_changed)
+];
+
+// Applies a change to the settings while holding their lock, so that concurrent changes can't
+// overwrite each other. Nothing is changed when update fails. The redraw is queued once the
+// lock is released.
+fn update_settings<F: FnOnce(&mut Settings) -> Result<()>>(context: &SessionContext, update: F) -> Result<()> {
+    let buffer_frames = {
+        let mut settings = context.settings.lock().unwrap();
+        let mut updated_settings = settings.clone();
+        update(&mut updated_settings)?;
+        *settings = updated_settings;
+        settings.buffer_frames
+    };
+
+    context.redraw_scheduler.set_buffer_frames(buffer_frames);
+    context.redraw_scheduler.queue_next_frame();
+    Ok(())
+}
+
+// Either every setting in the map is applied or, if any of them is invalid, none are
+fn set_settings(context: &SessionContext, arguments: &[Value]) -> Result<()> {
+    let new_values = match arguments {
+        [new_values] => parse_map(new_values)?,
+        _ => return Err(NotificationError::InvalidArguments(EventParseError::InvalidEventFormat))
+    };
+
+    update_settings(context, |settings| {
+        for (name, value) in new_values {
+            settings.set(parse_string(name)?, value)?;
+        }
+        Ok(())
+    })
+}
+
+fn setting_name(variable: &str) -> Option<&str> {
+    variable
+        .strip_prefix(SETTING_VARIABLE_PREFIX)
+        .filter(|name| SETTING_NAMES.contains(name))
+}
+
+// Sent by the g: watcher whenever a g:neovide_ variable is assigned or unlet
+fn setting_changed(context: &SessionContext, arguments: &[Value]) -> Result<()> {
+    let (variable, value) = match arguments {
+        [variable, value] => (parse_string(variable)?, value),
+        _ => return Err(NotificationError::InvalidArguments(EventParseError::InvalidEventFormat))
+    };
+
+    // Variables such as g:neovide_version are set by neovide itself and aren't settings
+    match setting_name(variable) {
+        Some(name) => update_settings(context, |settings| {
+            if value.is_nil() {
+                settings.reset(name)?;
+            } else {
+                settings.set(name, value)?;
+            }
+            Ok(())
+        }),
+        None => Ok(())
+    }
+}
+
+// Applies every g:neovide_ setting which was set before neovide attached. Invalid values are
+// skipped and reported one at a time so that a single typo doesn't discard the rest.
+pub async fn load_settings(nvim: &Neovim<NeovimWriter>, context: &SessionContext) -> std::result::Result<(), Box<CallError>> {
+    let variables = nvim.eval("filter(copy(g:), {name -> name =~# '^neovide_'})").await?;
+    let variables = match &variables {
+        Value::Map(variables) => variables.as_slice(),
+        _ => &[]
+    };
+
+    let mut errors = Vec::new();
+    update_settings(context, |settings| {
+        for (variable, value) in variables {
+            if let Some(name) = variable.as_str().and_then(setting_name) {
+                if let Err(error) = settings.set(name, value) {
+                    errors.push(error);
+                }
+            }
+        }
+        Ok(())
+    }).ok();
+
+    for error in errors {
+        nvim.err_writeln(&format!("Neovide: {}", error)).await?;
+    }
+    Ok(())
+}
+
+// Forwards later changes to any g:neovide_ variable to whichever channel neovide is attached on.
+// The watcher is only added once per neovim instance so that reattaching to a server doesn't
+// stack duplicates, and it stays quiet while no GUI is attached.
+pub fn setting_watcher_command() -> String {
+    format!(
+        "if !exists('g:NeovideSettingWatcher') | \
+            let g:NeovideSettingWatcher = {{dict, key, change -> \
+                empty(nvim_get_chan_info(g:neovide_channel_id)) ? 0 : \
+                rpcnotify(g:neovide_channel_id, 'neovide.setting_changed', key, get(change, 'new', v:null))}} | \
+            call dictwatcheradd(g:, '{}*', g:NeovideSettingWatcher) | \
+         endif",
+        SETTING_VARIABLE_PREFIX)
+}
+
+pub fn is_neovide_notification(name: &str) -> bool {
+    name.starts_with("neovide.")
+}
+
+pub fn handle_notification(context: &SessionContext, name: &str, arguments: &[Value]) -> Result<()> {
+    let (_, handler) = NOTIFICATION_HANDLERS
+        .iter()
+        .find(|(handler_name, _)| *handler_name == name)
+        .ok_or_else(|| NotificationError::UnknownNotification(name.to_string()))?;
+
+    handler(context, arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionOptions;
+
+    fn settings_map(entries: Vec<(&str, Value)>) -> Value {
+        Value::Map(entries.into_iter().map(|(name, value)| (Value::from(name), value)).collect())
+    }
+
+    #[test]
+    fn unknown_notifications_are_rejected() {
+        let context = SessionContext::new(SessionOptions::default());
+        match handle_notification(&context, "neovide.bogus", &[]) {
+            Err(NotificationError::UnknownNotification(name)) => assert_eq!(name, "neovide.bogus"),
+            other => panic!("expected an unknown notification error but got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn wrong_argument_counts_are_rejected() {
+        let context = SessionContext::new(SessionOptions::default());
+        let map = settings_map(vec![("padding", Value::from(10))]);
+        for (name, arguments) in vec![
+            ("neovide.set", vec![]),
+            ("neovide.set", vec![map.clone(), map]),
+            ("neovide.setting_changed", vec![Value::from("neovide_padding")])
+        ] {
+            match handle_notification(&context, name, &arguments) {
+                Err(NotificationError::InvalidArguments(_)) => {},
+                other => panic!("expected {} to reject {} arguments but got {:?}", name, arguments.len(), other)
+            }
+        }
+        assert_eq!(context.settings().padding, 0);
+    }
+
+    #[test]
+    fn set_applies_every_setting_in_the_map() {
+        let context = SessionContext::new(SessionOptions::default());
+        let map = settings_map(vec![("padding", Value::from(10)), ("cursor_animation", Value::from(false))]);
+        handle_notification(&context, "neovide.set", &[map]).unwrap();
+
+        let settings = context.settings();
+        assert_eq!(settings.padding, 10);
+        assert!(!settings.cursor_animation);
+    }
+
+    #[test]
+    fn set_changes_nothing_when_any_setting_is_invalid() {
+        let context = SessionContext::new(SessionOptions::default());
+        let map = settings_map(vec![("padding", Value::from(10)), ("refresh_rate", Value::from(1000))]);
+        match handle_notification(&context, "neovide.set", &[map]) {
+            Err(NotificationError::InvalidSetting(SettingError::InvalidValue { name, .. })) => assert_eq!(name, "refresh_rate"),
+            other => panic!("expected refresh_rate to be rejected but got {:?}", other)
+        }
+
+        let unknown = settings_map(vec![("padding", Value::from(10)), ("sparkles", Value::from(true))]);
+        assert!(handle_notification(&context, "neovide.set", &[unknown]).is_err());
+        assert_eq!(context.settings(), crate::settings::Settings::default());
+    }
+
+    #[test]
+    fn setting_changed_sets_and_unlets_variables() {
+        let context = SessionContext::new(SessionOptions::default());
+        handle_notification(&context, "neovide.setting_changed", &[Value::from("neovide_padding"), Value::from(20)]).unwrap();
+        assert_eq!(context.settings().padding, 20);
+
+        handle_notification(&context, "neovide.setting_changed", &[Value::from("neovide_padding"), Value::Nil]).unwrap();
+        assert_eq!(context.settings().padding, 0);
+
+        // Variables which aren't settings are ignored
+        handle_notification(&context, "neovide.setting_changed", &[Value::from("neovide_version"), Value::from("1")]).unwrap();
+    }
+}
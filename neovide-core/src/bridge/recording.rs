@@ -0,0 +1,197 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use log::error;
+use rmpv::Value;
+use rmpv::decode::{read_value, Error as DecodeError};
+use rmpv::encode::write_value;
+use tokio::time::delay_until;
+
+use crate::session::SessionContext;
+use super::events::{parse_array, parse_neovim_event, parse_string, RedrawEvent};
+use super::handler::NeovimHandler;
+use super::notifications::{handle_notification, is_neovide_notification};
+
+// A recording is a sequence of msgpack arrays, one per notification, each holding the seconds
+// since recording started, the notification name and its arguments.
+pub struct Recorder {
+    start: Instant,
+    writer: BufWriter<File>
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Recorder> {
+        Ok(Recorder {
+            start: Instant::now(),
+            writer: BufWriter::new(File::create(path)?)
+        })
+    }
+
+    fn record(&mut self, event_name: &str, arguments: &[Value]) -> io::Result<()> {
+        let frame = Value::Array(vec![
+            Value::from(self.start.elapsed().as_secs_f64()),
+            Value::from(event_name),
+            Value::Array(arguments.to_vec())
+        ]);
+        write_value(&mut self.writer, &frame).map_err(io::Error::from)?;
+        // Flushing at the end of every redraw batch keeps the recording usable up to the last
+        // complete screen when neovide crashes mid session
+        if ends_redraw_batch(event_name, arguments) {
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(error) = self.writer.flush() {
+            error!("Could not finish the recording: {}", error);
+        }
+    }
+}
+
+// Each redraw notification holds a list of events, each of them an array starting with its name
+fn ends_redraw_batch(event_name: &str, arguments: &[Value]) -> bool {
+    event_name == "redraw" && arguments.iter().any(|event| {
+        event.as_array()
+            .and_then(|event| event.first())
+            .and_then(Value::as_str) == Some("flush")
+    })
+}
+
+pub fn record_notification(context: &SessionContext, event_name: &str, arguments: &[Value]) {
+    if let Some(recorder) = &context.recorder {
+        if let Err(error) = recorder.lock().unwrap().record(event_name, arguments) {
+            error!("Could not record {}: {}", event_name, error);
+        }
+    }
+}
+
+struct Frame {
+    timestamp: Duration,
+    event_name: String,
+    arguments: Vec<Value>
+}
+
+fn read_frames(path: &Path) -> Result<Vec<Frame>, String> {
+    let data = fs::read(path)
+        .map_err(|error| format!("Could not read the recording {}: {}", path.display(), error))?;
+    let mut reader = &data[..];
+    let mut frames = Vec::new();
+
+    loop {
+        let value = match read_value(&mut reader) {
+            Ok(value) => value,
+            Err(DecodeError::InvalidMarkerRead(error)) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(format!("Corrupt frame {} in {}: {}", frames.len(), path.display(), error))
+        };
+
+        let invalid_frame = || format!("Invalid frame {} in {}", frames.len(), path.display());
+        let (timestamp, event_name, arguments) = match parse_array(&value).map_err(|_| invalid_frame())? {
+            [timestamp, event_name, arguments] => (timestamp, event_name, arguments),
+            _ => return Err(invalid_frame())
+        };
+
+        frames.push(Frame {
+            timestamp: Duration::from_secs_f64(timestamp.as_f64().ok_or_else(invalid_frame)?.max(0.0)),
+            event_name: parse_string(event_name).map_err(|_| invalid_frame())?.to_string(),
+            arguments: parse_array(arguments).map_err(|_| invalid_frame())?.to_vec()
+        });
+    }
+
+    Ok(frames)
+}
+
+fn parse_frame_events(context: &SessionContext, frame: &Frame) -> Result<Vec<RedrawEvent>, String> {
+    if is_neovide_notification(&frame.event_name) {
+        if let Err(error) = handle_notification(context, &frame.event_name, &frame.arguments) {
+            error!("Could not replay {}: {}", frame.event_name, error);
+        }
+        Ok(Vec::new())
+    } else {
+        parse_neovim_event(&frame.event_name, &frame.arguments)
+            .map_err(|error| format!("Could not parse {} from the recording: {}", frame.event_name, error))
+    }
+}
+
+// Applies a recording to the editor synchronously and without any runtime, calling on_flush with
+// the index of each batch once it has been applied.
+pub fn apply_recording<F>(context: &SessionContext, path: &Path, mut on_flush: F) -> Result<(), String>
+        where F: FnMut(usize) -> Result<(), String> {
+    let mut staged_events = Vec::new();
+    let mut flush_index = 0;
+
+    for frame in read_frames(path)? {
+        for event in parse_frame_events(context, &frame)? {
+            let flush = event.is_flush();
+            staged_events.push(event);
+
+            if flush {
+                {
+                    let mut editor = context.editor.lock().unwrap();
+                    for event in staged_events.drain(..) {
+                        editor.handle_redraw_event(event);
+                    }
+                }
+                on_flush(flush_index)?;
+                flush_index += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Feeds a recording through the same parsing and editor code as a live session, either with the
+// original timing or as fast as possible.
+pub async fn replay(path: &Path, realtime: bool, context: &SessionContext) -> Result<(), String> {
+    let frames = read_frames(path)?;
+    let handler = NeovimHandler::new(context.clone());
+    let start = tokio::time::Instant::now();
+
+    for frame in frames {
+        if realtime {
+            delay_until(start + frame.timestamp).await;
+        }
+
+        for event in parse_frame_events(context, &frame)? {
+            handler.handle_redraw_event(event)
+                .map_err(|error| format!("Could not process a recorded event: {}", error))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redraw(event_name: &str) -> Vec<Value> {
+        vec![Value::Array(vec![Value::from(event_name)])]
+    }
+
+    #[test]
+    fn recordings_are_flushed_at_the_end_of_each_redraw_batch_and_when_dropped() {
+        let path = std::env::temp_dir().join(format!("neovide-recording-test-{}", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+
+        recorder.record("redraw", &redraw("grid_line")).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        recorder.record("redraw", &redraw("flush")).unwrap();
+        assert_eq!(read_frames(&path).unwrap().len(), 2);
+
+        recorder.record("neovide.set_settings", &[]).unwrap();
+        assert_eq!(read_frames(&path).unwrap().len(), 2);
+        drop(recorder);
+        let frames = read_frames(&path).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[2].event_name, "neovide.set_settings");
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::error;
+use std::fmt;
+
+use rmpv::Value;
+
+use crate::clipboard::{ClipboardContents, ClipboardRegister, RegisterType};
+use crate::session::SessionContext;
+use super::events::{parse_array, parse_string, EventParseError};
+
+#[derive(Debug, Clone)]
+pub enum RequestError {
+    UnknownMethod(String),
+    WrongArgumentCount { method: String, expected: u64, received: usize },
+    InvalidArgument(EventParseError),
+    UnknownRegister(String),
+    Failed(String)
+}
+type Result<T> = std::result::Result<T, RequestError>;
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RequestError::UnknownMethod(method) => write!(f, "unknown neovide method {}", method),
+            RequestError::WrongArgumentCount { method, expected, received } => 
+                write!(f, "{} expects {} arguments but received {}", method, expected, received),
+            RequestError::InvalidArgument(error) => write!(f, "invalid argument: {}", error),
+            RequestError::UnknownRegister(register) => write!(f, "unknown clipboard register {}", register),
+            RequestError::Failed(message) => write!(f, "{}", message)
+        }
+    }
+}
+
+impl error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RequestError::InvalidArgument(error) => Some(error),
+            _ => None
+        }
+    }
+}
+
+impl From<EventParseError> for RequestError {
+    fn from(error: EventParseError) -> RequestError {
+        RequestError::InvalidArgument(error)
+    }
+}
+
+type RequestHandler = fn(&SessionContext, &[Value]) -> Result<Value>;
+
+// Every request neovide answers, as (method name, argument count, handler)
+pub const REQUEST_HANDLERS: &[(&str, u64, RequestHandler)] = &[
+    ("neovide.font_metrics", 0, font_metrics),
+    ("neovide.window_size", 0, window_size),
+    ("neovide.features", 0, features),
+    ("neovide.has_feature", 1, has_feature),
+    ("neovide.clipboard_get", 1, clipboard_get),
+    ("neovide.clipboard_set", 3, clipboard_set)
+];
+
+fn map(entries: Vec<(&str, Value)>) -> Value {
+    Value::Map(entries.into_iter().map(|(key, value)| (Value::from(key), value)).collect())
+}
+
+fn font_metrics(context: &SessionContext, _arguments: &[Value]) -> Result<Value> {
+    let editor = context.editor.lock().unwrap();
+    let (width, height) = editor.font_dimensions;
+    Ok(map(vec![
+        ("name", editor.font_name.clone().map(Value::from).unwrap_or(Value::Nil)),
+        ("size", editor.font_size.map(|size| Value::from(size as f64)).unwrap_or(Value::Nil)),
+        ("width", Value::from(width as f64)),
+        ("height", Value::from(height as f64))
+    ]))
+}
+
+fn window_size(context: &SessionContext, _arguments: &[Value]) -> Result<Value> {
+    let editor = context.editor.lock().unwrap();
+    let (columns, rows) = editor.size;
+    let (font_width, font_height) = editor.font_dimensions;
+    Ok(map(vec![
+        ("columns", Value::from(columns)),
+        ("rows", Value::from(rows)),
+        ("width", Value::from((columns as f32 * font_width) as f64)),
+        ("height", Value::from((rows as f32 * font_height) as f64))
+    ]))
+}
+
+fn supported_features(context: &SessionContext) -> Vec<&'static str> {
+    let ui_extensions = context.ui_extensions();
+    let mut features = vec!["ligatures", "animated_cursor", "emoji"];
+    if ui_extensions.multigrid {
+        features.push("multigrid");
+    }
+    if context.options.clipboard.is_enabled() {
+        features.push("clipboard");
+    }
+    features
+}
+
+fn features(context: &SessionContext, _arguments: &[Value]) -> Result<Value> {
+    Ok(Value::Array(supported_features(context).into_iter().map(Value::from).collect()))
+}
+
+fn has_feature(context: &SessionContext, arguments: &[Value]) -> Result<Value> {
+    let feature = parse_string(&arguments[0])?;
+    Ok(Value::from(supported_features(context).iter().any(|supported| *supported == feature)))
+}
+
+fn parse_register(value: &Value) -> Result<ClipboardRegister> {
+    let name = parse_string(value)?;
+    ClipboardRegister::parse(name).ok_or_else(|| RequestError::UnknownRegister(name.to_string()))
+}
+
+fn clipboard_get(context: &SessionContext, arguments: &[Value]) -> Result<Value> {
+    let register = parse_register(&arguments[0])?;
+    let contents = context.clipboard.lock().unwrap().get(register)
+        .map_err(|error| RequestError::Failed(error.to_string()))?;
+
+    Ok(Value::Array(vec![
+        Value::Array(contents.lines.iter().map(|line| Value::from(line.as_str())).collect()),
+        Value::from(contents.regtype())
+    ]))
+}
+
+fn clipboard_set(context: &SessionContext, arguments: &[Value]) -> Result<Value> {
+    let lines = parse_array(&arguments[0])?
+        .iter()
+        .map(|line| parse_string(line).map(String::from))
+        .collect::<std::result::Result<Vec<String>, EventParseError>>()?;
+    let register_type = RegisterType::parse(parse_string(&arguments[1])?);
+    let register = parse_register(&arguments[2])?;
+
+    context.clipboard.lock().unwrap().set(register, ClipboardContents { lines, register_type })
+        .map_err(|error| RequestError::Failed(error.to_string()))?;
+    Ok(Value::Nil)
+}
+
+// Only takes over the clipboard when the user hasn't configured one and neovim found no
+// clipboard tool of its own. Finding no tool marks the provider as loaded but broken, so it is
+// loaded again to pick up g:clipboard. The lambdas look the channel up on every call so that
+// they keep working after a reattach.
+pub fn clipboard_provider_command() -> String {
+    let copy = |register: &str| format!(
+        "'{0}': {{lines, regtype -> rpcrequest(g:neovide_channel_id, 'neovide.clipboard_set', lines, regtype, '{0}')}}",
+        register);
+    let paste = |register: &str| format!(
+        "'{0}': {{-> rpcrequest(g:neovide_channel_id, 'neovide.clipboard_get', '{0}')}}",
+        register);
+
+    format!(
+        "if !exists('g:clipboard') && empty(provider#clipboard#Executable()) | \
+            let g:clipboard = {{'name': 'neovide', 'copy': {{{}, {}}}, 'paste': {{{}, {}}}}} | \
+            unlet! g:loaded_clipboard_provider | \
+            runtime autoload/provider/clipboard.vim | \
+         endif",
+        copy("+"), copy("*"), paste("+"), paste("*"))
+}
+
+pub fn handle_request(context: &SessionContext, method: &str, arguments: &[Value]) -> Result<Value> {
+    let (_, expected, handler) = REQUEST_HANDLERS
+        .iter()
+        .find(|(name, _, _)| *name == method)
+        .ok_or_else(|| RequestError::UnknownMethod(method.to_string()))?;
+
+    if arguments.len() as u64 != *expected {
+        return Err(RequestError::WrongArgumentCount {
+            method: method.to_string(), expected: *expected, received: arguments.len()
+        });
+    }
+
+    handler(context, arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::ClipboardSetting;
+    use crate::session::SessionOptions;
+
+    fn memory_clipboard_context() -> SessionContext {
+        SessionContext::new(SessionOptions { clipboard: ClipboardSetting::Memory, ..SessionOptions::default() })
+    }
+
+    #[test]
+    fn clipboard_requests_round_trip_through_the_backend() {
+        let context = memory_clipboard_context();
+        let lines = Value::Array(vec![Value::from("first"), Value::from("second")]);
+        let set_result = handle_request(&context, "neovide.clipboard_set", &[lines.clone(), Value::from("V"), Value::from("+")]);
+        assert_eq!(set_result.unwrap(), Value::Nil);
+
+        let contents = handle_request(&context, "neovide.clipboard_get", &[Value::from("+")]).unwrap();
+        assert_eq!(contents, Value::Array(vec![lines, Value::from("V")]));
+    }
+
+    #[test]
+    fn clipboard_requests_reject_unknown_registers() {
+        let context = memory_clipboard_context();
+        match handle_request(&context, "neovide.clipboard_get", &[Value::from("a")]) {
+            Err(RequestError::UnknownRegister(register)) => assert_eq!(register, "a"),
+            other => panic!("expected an unknown register error but got {:?}", other)
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use nvim_rs::Neovim;
+use nvim_rs::error::CallError;
+
+use super::create::NeovimWriter;
+
+const PASTE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum UiCommand {
+    Resize { width: i64, height: i64 },
+    Keyboard(String),
+    MouseButton { action: String, grid: u64, position: (i64, i64) },
+    Scroll { direction: String, grid: u64, position: (i64, i64) },
+    Drag { grid: u64, position: (i64, i64) },
+    Paste(String),
+    Detach,
+    Restart
+}
+
+impl UiCommand {
+    pub async fn execute(self, nvim: &Neovim<NeovimWriter>) -> Result<(), Box<CallError>> {
+        match self {
+            UiCommand::Resize { width, height } => 
+                nvim.ui_try_resize(width.max(10), height.max(3)).await?,
+            UiCommand::Keyboard(input_command) => { 
+                nvim.input(&input_command).await?;
+            },
+            UiCommand::MouseButton { action, grid, position: (grid_x, grid_y) } => 
+                nvim.input_mouse("left", &action, "", grid as i64, grid_x, grid_y).await?,
+            UiCommand::Scroll { direction, grid, position: (grid_x, grid_y) } => 
+                nvim.input_mouse("wheel", &direction, "", grid as i64, grid_x, grid_y).await?,
+            UiCommand::Drag { grid, position: (grid_x, grid_y) } =>
+                nvim.input_mouse("left", "drag", "", grid as i64, grid_x, grid_y).await?,
+            UiCommand::Paste(text) => {
+                let chunks = paste_chunks(&text);
+                for (index, chunk) in chunks.iter().enumerate() {
+                    // A single call pastes everything at once, otherwise the first and last chunks
+                    // open and close the paste stream
+                    let phase = match index {
+                        _ if chunks.len() == 1 => -1,
+                        0 => 1,
+                        index if index == chunks.len() - 1 => 3,
+                        _ => 2
+                    };
+                    if !nvim.paste(chunk, true, phase).await? {
+                        // Neovim asked for the paste to be cancelled
+                        break;
+                    }
+                }
+            },
+            UiCommand::Detach => nvim.ui_detach().await?,
+            UiCommand::Restart => {}
+        };
+        Ok(())
+    }
+
+    pub fn is_resize(&self) -> bool {
+        match self {
+            UiCommand::Resize { .. } => true,
+            _ => false
+        }
+    }
+
+    pub fn is_detach(&self) -> bool {
+        match self {
+            UiCommand::Detach => true,
+            _ => false
+        }
+    }
+}
+
+// Splits text into chunks of at most PASTE_CHUNK_SIZE bytes without cutting a character in half
+fn paste_chunks(text: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut remaining = text;
+    while remaining.len() > PASTE_CHUNK_SIZE {
+        let mut split = PASTE_CHUNK_SIZE;
+        while !remaining.is_char_boundary(split) {
+            split -= 1;
+        }
+        let (chunk, rest) = remaining.split_at(split);
+        chunks.push(chunk);
+        remaining = rest;
+    }
+    chunks.push(remaining);
+    chunks
+}
+
+// Only the most recent resize in a batch matters, but every other command has to reach neovim
+// in the order the window system produced it.
+pub fn coalesce_resizes(commands: Vec<UiCommand>) -> Vec<UiCommand> {
+    let last_resize = commands.iter().rposition(UiCommand::is_resize);
+    commands
+        .into_iter()
+        .enumerate()
+        .filter(|(index, command)| !command.is_resize() || Some(*index) == last_resize)
+        .map(|(_, command)| command)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(commands: Vec<UiCommand>) -> Vec<String> {
+        commands.iter().map(|command| format!("{:?}", command)).collect()
+    }
+
+    #[test]
+    fn coalescing_keeps_the_last_resize_where_it_was_queued() {
+        let commands = vec![
+            UiCommand::Resize { width: 80, height: 24 },
+            UiCommand::Keyboard("a".to_string()),
+            UiCommand::Resize { width: 100, height: 30 },
+            UiCommand::Keyboard("b".to_string())
+        ];
+        assert_eq!(describe(coalesce_resizes(commands)), describe(vec![
+            UiCommand::Keyboard("a".to_string()),
+            UiCommand::Resize { width: 100, height: 30 },
+            UiCommand::Keyboard("b".to_string())
+        ]));
+    }
+
+    #[test]
+    fn coalescing_leaves_batches_without_resizes_alone() {
+        let commands = vec![UiCommand::Keyboard("a".to_string()), UiCommand::Paste("b".to_string())];
+        assert_eq!(describe(coalesce_resizes(commands.clone())), describe(commands));
+    }
+
+    // The leading ascii byte puts every chunk boundary in the middle of a two byte character
+    #[test]
+    fn paste_chunks_never_split_a_character() {
+        let text = format!("a{}", "é".repeat(PASTE_CHUNK_SIZE));
+        let chunks = paste_chunks(&text);
+        assert_eq!(chunks[0].len(), PASTE_CHUNK_SIZE - 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= PASTE_CHUNK_SIZE));
+        assert_eq!(chunks.concat(), text);
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClipboardRegister {
+    Clipboard,
+    Selection
+}
+
+impl ClipboardRegister {
+    pub fn parse(name: &str) -> Option<ClipboardRegister> {
+        match name {
+            "+" => Some(ClipboardRegister::Clipboard),
+            "*" => Some(ClipboardRegister::Selection),
+            _ => None
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            ClipboardRegister::Clipboard => "plus",
+            ClipboardRegister::Selection => "star"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterType {
+    Charwise,
+    Linewise,
+    Blockwise(Option<u64>)
+}
+
+impl RegisterType {
+    // Accepts both the setreg() spellings and the getregtype() ones, which prefix the block width
+    // with a literal ctrl-v.
+    pub fn parse(regtype: &str) -> Option<RegisterType> {
+        let mut characters = regtype.chars();
+        match characters.next() {
+            Some('v') | Some('c') => Some(RegisterType::Charwise),
+            Some('V') | Some('l') => Some(RegisterType::Linewise),
+            Some('b') | Some('\u{16}') => Some(RegisterType::Blockwise(characters.as_str().parse().ok())),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for RegisterType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegisterType::Charwise => write!(f, "v"),
+            RegisterType::Linewise => write!(f, "V"),
+            RegisterType::Blockwise(Some(width)) => write!(f, "b{}", width),
+            RegisterType::Blockwise(None) => write!(f, "b")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipboardContents {
+    pub lines: Vec<String>,
+    pub register_type: Option<RegisterType>
+}
+
+impl ClipboardContents {
+    pub fn from_text(text: &str) -> ClipboardContents {
+        ClipboardContents {
+            lines: text.split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect(),
+            register_type: None
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    // An empty regtype lets neovim work out the register type from the text itself
+    pub fn regtype(&self) -> String {
+        self.register_type.map(|register_type| register_type.to_string()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipboardError(pub String);
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "clipboard error: {}", self.0)
+    }
+}
+
+pub trait ClipboardBackend: Send {
+    fn get(&mut self, register: ClipboardRegister) -> Result<ClipboardContents, ClipboardError>;
+    fn set(&mut self, register: ClipboardRegister, contents: ClipboardContents) -> Result<(), ClipboardError>;
+}
+
+#[derive(Default)]
+pub struct MemoryClipboard {
+    registers: HashMap<ClipboardRegister, ClipboardContents>
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn get(&mut self, register: ClipboardRegister) -> Result<ClipboardContents, ClipboardError> {
+        Ok(self.registers.get(&register).cloned().unwrap_or_else(|| ClipboardContents::from_text("")))
+    }
+
+    fn set(&mut self, register: ClipboardRegister, contents: ClipboardContents) -> Result<(), ClipboardError> {
+        self.registers.insert(register, contents);
+        Ok(())
+    }
+}
+
+// Keeps each register in its own file inside a directory. The first line of a file holds the
+// register type and the rest hold the register's lines.
+pub struct FileClipboard {
+    directory: PathBuf
+}
+
+impl FileClipboard {
+    pub fn new(directory: PathBuf) -> FileClipboard {
+        FileClipboard { directory }
+    }
+}
+
+impl ClipboardBackend for FileClipboard {
+    fn get(&mut self, register: ClipboardRegister) -> Result<ClipboardContents, ClipboardError> {
+        let path = self.directory.join(register.file_name());
+        if !path.exists() {
+            return Ok(ClipboardContents::from_text(""));
+        }
+
+        let stored = fs::read_to_string(&path)
+            .map_err(|error| ClipboardError(format!("could not read {}: {}", path.display(), error)))?;
+        let (regtype, text) = match stored.find('\n') {
+            Some(index) => (&stored[..index], &stored[index + 1..]),
+            None => (stored.as_str(), "")
+        };
+
+        Ok(ClipboardContents {
+            register_type: RegisterType::parse(regtype),
+            ..ClipboardContents::from_text(text)
+        })
+    }
+
+    fn set(&mut self, register: ClipboardRegister, contents: ClipboardContents) -> Result<(), ClipboardError> {
+        let path = self.directory.join(register.file_name());
+        fs::create_dir_all(&self.directory)
+            .and_then(|_| fs::write(&path, format!("{}\n{}", contents.regtype(), contents.text())))
+            .map_err(|error| ClipboardError(format!("could not write {}: {}", path.display(), error)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClipboardSetting {
+    System,
+    Memory,
+    File(PathBuf),
+    Disabled
+}
+
+impl Default for ClipboardSetting {
+    fn default() -> ClipboardSetting {
+        ClipboardSetting::System
+    }
+}
+
+impl ClipboardSetting {
+    pub fn parse(value: &str) -> Option<ClipboardSetting> {
+        match value {
+            "system" => Some(ClipboardSetting::System),
+            "memory" => Some(ClipboardSetting::Memory),
+            "none" => Some(ClipboardSetting::Disabled),
+            _ if value.starts_with("file:") && value.len() > "file:".len() =>
+                Some(ClipboardSetting::File(PathBuf::from(&value["file:".len()..]))),
+            _ => None
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            ClipboardSetting::Disabled => false,
+            _ => true
+        }
+    }
+
+    // The system clipboard belongs to the frontend, so there is no backend for it here
+    pub fn create_backend(&self) -> Option<Box<dyn ClipboardBackend>> {
+        match self {
+            ClipboardSetting::System => None,
+            ClipboardSetting::File(directory) => Some(Box::new(FileClipboard::new(directory.clone()))),
+            ClipboardSetting::Memory | ClipboardSetting::Disabled => Some(Box::new(MemoryClipboard::default()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blockwise_contents() -> ClipboardContents {
+        ClipboardContents {
+            lines: vec!["ab".to_string(), "cd".to_string()],
+            register_type: Some(RegisterType::Blockwise(Some(2)))
+        }
+    }
+
+    #[test]
+    fn register_types_round_trip_through_their_names() {
+        for register_type in [RegisterType::Charwise, RegisterType::Linewise, RegisterType::Blockwise(Some(3)), RegisterType::Blockwise(None)].iter() {
+            assert_eq!(RegisterType::parse(&register_type.to_string()), Some(*register_type));
+        }
+        assert_eq!(RegisterType::parse("\u{16}12"), Some(RegisterType::Blockwise(Some(12))));
+    }
+
+    #[test]
+    fn memory_clipboard_keeps_registers_apart() {
+        let mut clipboard = MemoryClipboard::default();
+        assert_eq!(clipboard.get(ClipboardRegister::Clipboard).unwrap(), ClipboardContents::from_text(""));
+
+        clipboard.set(ClipboardRegister::Clipboard, blockwise_contents()).unwrap();
+        assert_eq!(clipboard.get(ClipboardRegister::Clipboard).unwrap(), blockwise_contents());
+        assert_eq!(clipboard.get(ClipboardRegister::Selection).unwrap(), ClipboardContents::from_text(""));
+    }
+
+    #[test]
+    fn file_clipboard_round_trips_lines_and_register_type() {
+        let directory = std::env::temp_dir().join(format!("neovide-clipboard-test-{}", std::process::id()));
+        let mut clipboard = FileClipboard::new(directory.clone());
+        assert_eq!(clipboard.get(ClipboardRegister::Selection).unwrap(), ClipboardContents::from_text(""));
+
+        clipboard.set(ClipboardRegister::Selection, blockwise_contents()).unwrap();
+        let linewise = ClipboardContents { lines: vec!["line".to_string(), "".to_string()], register_type: Some(RegisterType::Linewise) };
+        clipboard.set(ClipboardRegister::Clipboard, linewise.clone()).unwrap();
+
+        // A new backend over the same directory sees what the first one stored
+        let mut reopened = FileClipboard::new(directory.clone());
+        assert_eq!(reopened.get(ClipboardRegister::Selection).unwrap(), blockwise_contents());
+        assert_eq!(reopened.get(ClipboardRegister::Clipboard).unwrap(), linewise);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn clipboard_settings_parse() {
+        assert_eq!(ClipboardSetting::parse("file:/tmp/clip"), Some(ClipboardSetting::File(PathBuf::from("/tmp/clip"))));
+        assert_eq!(ClipboardSetting::parse("file:"), None);
+        assert_eq!(ClipboardSetting::parse("none"), Some(ClipboardSetting::Disabled));
+    }
+}
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
-use skulpin::skia_safe::Color4f;
-
-use super::style::{Style, Colors};
+use super::style::{Color, Style, Colors};
+use super::GLOBAL_GRID;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CursorShape {
@@ -33,6 +32,7 @@ pub struct CursorMode {
 
 #[derive(Clone, PartialEq)]
 pub struct Cursor {
+    pub grid: u64,
     pub position: (u64, u64),
     pub shape: CursorShape,
     pub cell_percentage: Option<f32>,
@@ -47,6 +47,7 @@ pub struct Cursor {
 impl Cursor {
     pub fn new() -> Cursor {
         Cursor {
+            grid: GLOBAL_GRID,
             position: (0, 0),
             shape: CursorShape::Block,
             style: None,
@@ -59,7 +60,7 @@ impl Cursor {
         }
     }
 
-    pub fn foreground(&self, default_colors: &Colors) -> Color4f {
+    pub fn foreground(&self, default_colors: &Colors) -> Color {
         if let Some(style) = &self.style {
             style.colors.foreground.clone().unwrap_or(default_colors.background.clone().unwrap())
         } else {
@@ -67,7 +68,7 @@ impl Cursor {
         }
     }
 
-    pub fn background(&self, default_colors: &Colors) -> Color4f {
+    pub fn background(&self, default_colors: &Colors) -> Color {
         if let Some(style) = &self.style {
             style.colors.background.clone().unwrap_or(default_colors.foreground.clone().unwrap())
         } else {
@@ -0,0 +1,157 @@
+use super::{CharacterGrid, Color, Colors, Editor, GridCell, Style};
+
+fn describe_color(color: &Option<Color>) -> String {
+    match color {
+        Some(color) => format!(
+            "#{:02x}{:02x}{:02x}",
+            (color.r * 255.0).round() as u8, (color.g * 255.0).round() as u8, (color.b * 255.0).round() as u8),
+        None => "-".to_string()
+    }
+}
+
+fn describe_style(style: &Option<Style>) -> String {
+    match style {
+        Some(style) => {
+            let mut description = format!(
+                "fg={} bg={} sp={}",
+                describe_color(&style.colors.foreground),
+                describe_color(&style.colors.background),
+                describe_color(&style.colors.special));
+            let flags = [
+                ("reverse", style.reverse), ("italic", style.italic), ("bold", style.bold),
+                ("strikethrough", style.strikethrough), ("underline", style.underline), ("undercurl", style.undercurl)
+            ];
+            for (name, _) in flags.iter().filter(|(_, set)| *set) {
+                description.push(' ');
+                description.push_str(name);
+            }
+            if style.blend > 0 {
+                description.push_str(&format!(" blend={}", style.blend));
+            }
+            description
+        },
+        None => "default".to_string()
+    }
+}
+
+fn cell_style(cell: &GridCell) -> Option<Style> {
+    cell.as_ref().and_then(|(_, style)| style.clone())
+}
+
+// Groups consecutive columns for which key returns the same value into (start, end, value) runs
+fn runs<T: PartialEq, F: Fn(usize) -> T>(length: usize, key: F) -> Vec<(usize, usize, T)> {
+    let mut runs: Vec<(usize, usize, T)> = Vec::new();
+    for index in 0..length {
+        let value = key(index);
+        match runs.last_mut() {
+            Some((_, end, last_value)) if *last_value == value => *end = index,
+            _ => runs.push((index, index, value))
+        }
+    }
+    runs
+}
+
+impl CharacterGrid {
+    fn dump(&mut self, grid_id: u64, default_colors: &Colors, lines: &mut Vec<String>) {
+        let (column, row) = self.position;
+        let floating = match &self.floating {
+            Some(floating) => format!(
+                " float {} on grid {}{}{}",
+                floating.order, floating.anchor_grid,
+                if floating.focusable { "" } else { " unfocusable" },
+                if floating.popup_menu { " popup menu" } else { "" }),
+            None => "".to_string()
+        };
+        let visibility = if self.visible { "" } else { " hidden" };
+        lines.push(format!("grid {} {}x{} at {},{}{}{}:", grid_id, self.width, self.height, column, row, floating, visibility));
+        for row in self.characters.iter() {
+            let text: String = row.iter()
+                .map(|cell| cell.as_ref().map(|(character, _)| character.as_str()).unwrap_or(" "))
+                .collect();
+            lines.push(format!("|{}|", text));
+        }
+
+        lines.push("styles:".to_string());
+        for (row_index, row) in self.characters.iter().enumerate() {
+            let style_runs = runs(row.len(), |column| cell_style(&row[column]));
+            if style_runs.iter().any(|(_, _, style)| style.is_some()) {
+                let descriptions: Vec<String> = style_runs.iter()
+                    .map(|(start, end, style)| format!("{}-{} {}", start, end, describe_style(style)))
+                    .collect();
+                lines.push(format!("row {}: {}", row_index, descriptions.join(", ")));
+            }
+        }
+
+        lines.push("dirty:".to_string());
+        for (row_index, dirty_row) in self.dirty.iter().enumerate() {
+            let dirty_runs: Vec<String> = runs(dirty_row.len(), |column| dirty_row[column])
+                .into_iter()
+                .filter(|(_, _, dirty)| *dirty)
+                .map(|(start, end, _)| format!("{}-{}", start, end))
+                .collect();
+            if !dirty_runs.is_empty() {
+                lines.push(format!("row {}: {}", row_index, dirty_runs.join(", ")));
+            }
+        }
+
+        let (draw_commands, should_clear) = self.build_draw_commands(default_colors);
+        lines.push(format!("draw commands: clear={}", should_clear));
+        for command in draw_commands {
+            let (column, row) = command.grid_position;
+            lines.push(format!(
+                "{},{} x{} {:?} {}",
+                column, row, command.scale, command.text, describe_style(&command.style)));
+        }
+    }
+}
+
+impl Editor {
+    // Writes out the text, cell styles, dirty cells and draw commands of every grid in a stable
+    // text form, so that two editor states can be compared with a line diff. Building the draw
+    // commands resets the dirty cells just as rendering a frame would.
+    pub fn dump_state(&mut self) -> String {
+        let (width, height) = self.size;
+        let mut lines = vec![
+            format!("size {}x{}", width, height),
+            format!("cursor grid {} at {},{}", self.cursor.grid, self.cursor.position.0, self.cursor.position.1),
+            format!("default {}", describe_style(&Some(Style::new(self.default_colors.clone()))))
+        ];
+
+        for grid_id in self.draw_order() {
+            let grid = self.grids.get_mut(&grid_id).unwrap();
+            grid.dump(grid_id, &self.default_colors, &mut lines);
+        }
+
+        let mut dump = lines.join("\n");
+        dump.push('\n');
+        dump
+    }
+}
+
+// Compares two dumps line by line and describes every differing line, or returns None when they
+// match.
+pub fn diff_dumps(expected: &str, actual: &str) -> Option<String> {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let mut differences = Vec::new();
+
+    for index in 0..expected_lines.len().max(actual_lines.len()) {
+        let expected_line = expected_lines.get(index);
+        let actual_line = actual_lines.get(index);
+        if expected_line != actual_line {
+            differences.push(format!("line {}:", index + 1));
+            if let Some(expected_line) = expected_line {
+                differences.push(format!("- {}", expected_line));
+            }
+            if let Some(actual_line) = actual_line {
+                differences.push(format!("+ {}", actual_line));
+            }
+        }
+    }
+
+    if differences.is_empty() {
+        None
+    } else {
+        Some(differences.join("\n"))
+    }
+}
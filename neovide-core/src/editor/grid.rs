@@ -0,0 +1,214 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Colors, DrawCommand, Style};
+
+pub type GridCell = Option<(String, Option<Style>)>;
+
+// What neovim told us about a floating window, along with its place in the stack of floats
+#[derive(Debug, Clone, PartialEq)]
+pub struct FloatingWindow {
+    pub order: u64,
+    pub anchor_grid: u64,
+    pub focusable: bool,
+    pub popup_menu: bool
+}
+
+// One grid of cells as neovim sees it. Without ext_multigrid everything is drawn into the global
+// grid, with it every window gets a grid of its own which is placed over the global grid at
+// position, given in cells as (column, row). Floating windows are placed the same way, but are
+// drawn above every other window.
+pub struct CharacterGrid {
+    pub width: u64,
+    pub height: u64,
+    pub characters: Vec<Vec<GridCell>>,
+    pub dirty: Vec<Vec<bool>>,
+    pub should_clear: bool,
+    pub position: (u64, u64),
+    pub visible: bool,
+    pub floating: Option<FloatingWindow>
+}
+
+impl CharacterGrid {
+    pub fn new(size: (u64, u64)) -> CharacterGrid {
+        let mut grid = CharacterGrid {
+            width: 0,
+            height: 0,
+            characters: Vec::new(),
+            dirty: Vec::new(),
+            should_clear: true,
+            position: (0, 0),
+            visible: false,
+            floating: None
+        };
+
+        grid.resize(size);
+        grid
+    }
+
+    pub fn size(&self) -> (u64, u64) {
+        (self.width, self.height)
+    }
+
+    pub fn resize(&mut self, (width, height): (u64, u64)) {
+        self.width = width;
+        self.height = height;
+        self.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.characters = vec![vec![None; self.width as usize]; self.height as usize];
+        self.dirty = vec![vec![true; self.width as usize]; self.height as usize];
+        self.should_clear = true;
+    }
+
+    pub fn contains(&self, (x, y): (u64, u64)) -> bool {
+        let (left, top) = self.position;
+        x >= left && x < left + self.width && y >= top && y < top + self.height
+    }
+
+    // Marks every cell dirty so that the next draw commands cover the whole grid
+    pub fn invalidate(&mut self) {
+        self.dirty = vec![vec![true; self.width as usize]; self.height as usize];
+        self.should_clear = true;
+    }
+
+    pub fn get_cell(&self, x: u64, y: u64) -> Option<&GridCell> {
+        self.characters
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+    }
+
+    // Writes text starting at column_pos and moves column_pos past it. An empty string is the
+    // right half of a double width character and takes up a single cell.
+    pub fn set_text(&mut self, row_index: u64, column_pos: &mut u64, text: &str, style: &Option<Style>) {
+        let row = &mut self.characters[row_index as usize];
+        let dirty_row = &mut self.dirty[row_index as usize];
+
+        if text.is_empty() {
+            if let Some(cell) = row.get_mut(*column_pos as usize) {
+                *cell = Some(("".to_string(), style.clone()));
+                dirty_row[*column_pos as usize] = true;
+            }
+            *column_pos += 1;
+        } else {
+            for (i, character) in text.graphemes(true).enumerate() {
+                let pointer_index = i + *column_pos as usize;
+                if pointer_index < row.len() {
+                    row[pointer_index] = Some((character.to_string(), style.clone()));
+                    dirty_row[pointer_index] = true;
+                }
+            }
+            *column_pos += text.graphemes(true).count() as u64;
+        }
+    }
+
+    pub fn scroll_region(&mut self, top: u64, bot: u64, left: u64, right: u64, rows: i64, cols: i64) {
+        let (top, bot) =  if rows > 0 {
+            (top as i64 + rows, bot as i64)
+        } else if rows < 0 {
+            (top as i64, bot as i64 + rows)
+        } else {
+            (top as i64, bot as i64)
+        };
+
+        let (left, right) = if cols > 0 {
+            (left as i64 + cols, right as i64)
+        } else if cols < 0 {
+            (left as i64, right as i64 + cols)
+        } else {
+            (left as i64, right as i64)
+        };
+
+        let mut region = Vec::new();
+        for y in top..bot {
+            let row = &self.characters[y as usize];
+            let mut copied_section = Vec::new();
+            for x in left..right {
+                copied_section.push(row[x as usize].clone());
+            }
+            region.push(copied_section);
+        }
+
+        let new_top = top as i64 - rows;
+        let new_left = left as i64 - cols;
+
+        for (y, row_section) in region.into_iter().enumerate() {
+            for (x, cell) in row_section.into_iter().enumerate() {
+                let y = new_top + y as i64;
+                if y >= 0 && y < self.characters.len() as i64 {
+                    let row = &mut self.characters[y as usize];
+                    let dirty_row = &mut self.dirty[y as usize];
+                    let x = new_left + x as i64;
+                    if x >= 0 && x < row.len() as i64 {
+                        row[x as usize] = cell;
+                        dirty_row[x as usize] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    // Builds the commands for every run of cells which changed since the last call and resets
+    // the dirty state
+    pub fn build_draw_commands(&mut self, default_colors: &Colors) -> (Vec<DrawCommand>, bool) {
+        let mut draw_commands = Vec::new();
+        for (row_index, row) in self.characters.iter().enumerate() {
+            let mut command = None;
+
+            fn add_command(commands_list: &mut Vec<DrawCommand>, command: Option<DrawCommand>) {
+                if let Some(command) = command {
+                    commands_list.push(command);
+                }
+            }
+
+            fn command_matches(command: &Option<DrawCommand>, style: &Option<Style>) -> bool {
+                match command {
+                    Some(command) => &command.style == style,
+                    None => true
+                }
+            }
+
+            fn add_character(command: &mut Option<DrawCommand>, character: &str, row_index: u64, col_index: u64, style: Option<Style>) {
+                match command {
+                    Some(command) => command.text.push_str(character),
+                    None => {
+                        command.replace(DrawCommand::new(character.to_string(), (col_index, row_index), style));
+                    }
+                }
+            }
+
+            for (col_index, cell) in row.iter().enumerate() {
+                let (character, style) = cell.clone().unwrap_or_else(|| (' '.to_string(), Some(Style::new(default_colors.clone()))));
+                if character.is_empty() {
+                    add_character(&mut command, &" ", row_index as u64, col_index as u64, style.clone());
+                    add_command(&mut draw_commands, command);
+                    command = None;
+                } else {
+                    if !command_matches(&command, &style) {
+                        add_command(&mut draw_commands, command);
+                        command = None;
+                    }
+                    add_character(&mut command, &character, row_index as u64, col_index as u64, style.clone());
+                }
+            }
+            add_command(&mut draw_commands, command);
+        }
+        let should_clear = self.should_clear;
+
+        let draw_commands = draw_commands.into_iter().filter(|command| {
+            let (x, y) = command.grid_position;
+            let dirty_row = &self.dirty[y as usize];
+
+            for char_index in 0..command.text.graphemes(true).count() {
+                if dirty_row[x as usize + char_index] {
+                    return true;
+                }
+            }
+            return false;
+        }).collect::<Vec<DrawCommand>>();
+
+        self.dirty = vec![vec![false; self.width as usize]; self.height as usize];
+        self.should_clear = false;
+        (draw_commands, should_clear)
+    }
+}
@@ -0,0 +1,318 @@
+mod cursor;
+mod dump;
+mod grid;
+mod style;
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+
+use log::warn;
+
+pub use cursor::{Cursor, CursorShape, CursorMode};
+pub use style::{Color, Colors, Style};
+pub use dump::diff_dumps;
+pub use grid::{CharacterGrid, FloatingWindow, GridCell};
+use crate::bridge::{GridLineCell, GuiOption, RedrawEvent, WindowAnchor};
+
+// Neovim's id for the grid which spans the whole screen
+pub const GLOBAL_GRID: u64 = 1;
+
+#[derive(new, Debug, Clone)]
+pub struct DrawCommand {
+    pub text: String,
+    pub grid_position: (u64, u64),
+    pub style: Option<Style>,
+    #[new(value = "1")]
+    pub scale: u16
+}
+
+// The changes to one grid since the last frame, along with where it goes on the screen
+pub struct GridDrawCommands {
+    pub grid: u64,
+    pub size: (u64, u64),
+    pub position: (u64, u64),
+    pub visible: bool,
+    pub floating: Option<FloatingWindow>,
+    pub commands: Vec<DrawCommand>,
+    pub should_clear: bool
+}
+
+pub struct Editor {
+    pub grids: HashMap<u64, CharacterGrid>,
+    pub message_grid: Option<u64>,
+    next_float_order: u64,
+
+    pub title: String,
+    pub size: (u64, u64),
+    pub font_name: Option<String>,
+    pub font_size: Option<f32>,
+    pub font_dimensions: (f32, f32),
+    pub cursor: Cursor,
+    pub default_colors: Colors,
+    pub defined_styles: HashMap<u64, Style>,
+    pub previous_style: Option<Style>,
+    pub popup_menu_blend: u8
+}
+
+impl Editor {
+    pub fn new(size: (u64, u64)) -> Editor {
+        let mut global_grid = CharacterGrid::new(size);
+        global_grid.visible = true;
+        let mut grids = HashMap::new();
+        grids.insert(GLOBAL_GRID, global_grid);
+
+        Editor {
+            grids,
+            message_grid: None,
+            next_float_order: 0,
+
+            title: "Neovide".to_string(),
+            cursor: Cursor::new(),
+            size,
+            font_name: None,
+            font_size: None,
+            font_dimensions: (0.0, 0.0),
+            default_colors: Colors::new(Some(Color::WHITE), Some(Color::BLACK), Some(Color::GREY)),
+            defined_styles: HashMap::new(),
+            previous_style: None,
+            popup_menu_blend: 0
+        }
+    }
+
+    pub fn handle_redraw_event(&mut self, event: RedrawEvent) {
+        match event {
+            RedrawEvent::SetTitle { title } => self.title = title,
+            RedrawEvent::ModeInfoSet { cursor_modes } => self.cursor.mode_list = cursor_modes,
+            RedrawEvent::OptionSet { gui_option } => self.set_option(gui_option),
+            RedrawEvent::ModeChange { mode_index } => self.cursor.change_mode(mode_index, &self.defined_styles),
+            RedrawEvent::BusyStart => self.cursor.enabled = false,
+            RedrawEvent::BusyStop => self.cursor.enabled = true,
+            RedrawEvent::Resize { grid, width, height } => self.resize_grid(grid, (width, height)),
+            RedrawEvent::DefaultColorsSet { colors } => self.default_colors = colors,
+            RedrawEvent::HighlightAttributesDefine { id, style } => { self.defined_styles.insert(id, style); },
+            RedrawEvent::GridLine { grid, row, column_start, cells } => self.draw_grid_line(grid, row, column_start, cells),
+            RedrawEvent::Clear { grid } => self.clear_grid(grid),
+            RedrawEvent::CursorGoto { grid, row, column } => {
+                self.cursor.grid = grid;
+                self.cursor.position = (row, column);
+            },
+            RedrawEvent::Scroll { grid, top, bottom, left, right, rows, columns } => {
+                if let Some(grid) = self.grids.get_mut(&grid) {
+                    grid.scroll_region(top, bottom, left, right, rows, columns);
+                }
+            },
+            RedrawEvent::Destroy { grid } => self.destroy_grid(grid),
+            RedrawEvent::WindowPosition { grid, start_row, start_column, width, height, .. } =>
+                self.position_window(grid, (start_column, start_row), (width, height)),
+            RedrawEvent::WindowFloatPosition { grid, window, anchor, anchor_grid, anchor_row, anchor_column, focusable } =>
+                self.position_float(grid, window, anchor, anchor_grid, (anchor_column, anchor_row), focusable),
+            RedrawEvent::WindowHide { grid } | RedrawEvent::WindowClose { grid } => self.hide_window(grid),
+            RedrawEvent::MessageSetPosition { grid, row, .. } => self.position_message_grid(grid, row),
+            _ => {}
+        };
+    }
+
+    // Every grid id from the bottom of the screen to the top. Window grids are drawn over the
+    // global grid, floats over the windows in the order they were first shown, and the message
+    // grid over everything else.
+    pub fn draw_order(&self) -> Vec<u64> {
+        let mut grid_ids: Vec<u64> = self.grids.keys().cloned().collect();
+        grid_ids.sort_by_key(|grid_id| {
+            let grid = &self.grids[grid_id];
+            if *grid_id == GLOBAL_GRID {
+                (0, 0)
+            } else if Some(*grid_id) == self.message_grid {
+                (3, *grid_id)
+            } else if let Some(floating) = &grid.floating {
+                (2, floating.order)
+            } else {
+                (1, *grid_id)
+            }
+        });
+        grid_ids
+    }
+
+    // The draw commands for every grid in draw order. Hidden grids are included so that they
+    // are up to date when they are shown again.
+    pub fn build_draw_commands(&mut self) -> Vec<GridDrawCommands> {
+        let mut grid_draw_commands = Vec::new();
+        for grid_id in self.draw_order() {
+            let default_colors = &self.default_colors;
+            let grid = self.grids.get_mut(&grid_id).unwrap();
+            let (commands, should_clear) = grid.build_draw_commands(default_colors);
+            grid_draw_commands.push(GridDrawCommands {
+                grid: grid_id,
+                size: grid.size(),
+                position: grid.position,
+                visible: grid.visible,
+                floating: grid.floating.clone(),
+                commands,
+                should_clear
+            });
+        }
+        grid_draw_commands
+    }
+
+    // The topmost visible grid covering a screen position given as (column, row)
+    pub fn grid_at(&self, position: (u64, u64)) -> Option<u64> {
+        self.draw_order()
+            .into_iter()
+            .rev()
+            .find(|grid_id| {
+                let grid = &self.grids[grid_id];
+                grid.visible && grid.contains(position)
+            })
+    }
+
+    // The cell shown at a screen position, looked up in whichever grid is on top there
+    pub fn visible_cell(&self, (x, y): (u64, u64)) -> Option<&GridCell> {
+        let grid = &self.grids[&self.grid_at((x, y))?];
+        let (left, top) = grid.position;
+        grid.get_cell(x - left, y - top)
+    }
+
+    // The cursor position is relative to the grid the cursor is on, so it is offset by where
+    // that grid is placed on the screen
+    pub fn cursor_screen_position(&self) -> (u64, u64) {
+        let (x, y) = self.cursor.position;
+        let (left, top) = self.grids.get(&self.cursor.grid)
+            .map(|grid| grid.position)
+            .unwrap_or((0, 0));
+        (x + left, y + top)
+    }
+
+    fn draw_grid_line(&mut self, grid_id: u64, row: u64, column_start: u64, cells: Vec<GridLineCell>) {
+        let grid = match self.grids.get_mut(&grid_id) {
+            Some(grid) if row < grid.height => grid,
+            _ => {
+                warn!("Draw command out of bounds: grid {} row {} column {}", grid_id, row, column_start);
+                return;
+            }
+        };
+
+        let mut column_pos = column_start;
+        for cell in cells {
+            let style = match cell.highlight_id {
+                Some(0) => None,
+                Some(style_id) => self.defined_styles.get(&style_id).cloned(),
+                None => self.previous_style.clone()
+            };
+
+            let mut text = cell.text;
+            if let Some(times) = cell.repeat {
+                text = text.repeat(times as usize);
+            }
+
+            grid.set_text(row, &mut column_pos, &text, &style);
+            self.previous_style = style;
+        }
+    }
+
+    fn resize_grid(&mut self, grid_id: u64, new_size: (u64, u64)) {
+        if grid_id == GLOBAL_GRID {
+            self.size = new_size;
+        }
+        match self.grids.get_mut(&grid_id) {
+            Some(grid) => grid.resize(new_size),
+            None => { self.grids.insert(grid_id, CharacterGrid::new(new_size)); }
+        }
+    }
+
+    fn clear_grid(&mut self, grid_id: u64) {
+        if let Some(grid) = self.grids.get_mut(&grid_id) {
+            grid.clear();
+        }
+    }
+
+    fn position_window(&mut self, grid_id: u64, position: (u64, u64), size: (u64, u64)) {
+        let grid = self.grids.entry(grid_id).or_insert_with(|| CharacterGrid::new(size));
+        grid.position = position;
+        grid.visible = true;
+        // Floats are drawn over a transparent background, so a float turning into a normal
+        // window needs drawing again from scratch, and so does the reverse
+        if grid.floating.take().is_some() {
+            grid.invalidate();
+        }
+    }
+
+    // Places a float by one of its corners at a point on its anchor grid, in cells which may be
+    // fractional, and keeps it on the screen
+    fn position_float(&mut self, grid_id: u64, window: i64, anchor: WindowAnchor, anchor_grid: u64, (anchor_column, anchor_row): (f64, f64), focusable: bool) {
+        let (anchor_left, anchor_top) = self.grids.get(&anchor_grid)
+            .map(|grid| grid.position)
+            .unwrap_or((0, 0));
+        let (screen_width, screen_height) = self.size;
+        let next_float_order = &mut self.next_float_order;
+        let grid = match self.grids.get_mut(&grid_id) {
+            Some(grid) => grid,
+            None => return
+        };
+
+        let (width, height) = (grid.width as f64, grid.height as f64);
+        let (left, top) = match anchor {
+            WindowAnchor::NorthWest => (anchor_column, anchor_row),
+            WindowAnchor::NorthEast => (anchor_column - width, anchor_row),
+            WindowAnchor::SouthWest => (anchor_column, anchor_row - height),
+            WindowAnchor::SouthEast => (anchor_column - width, anchor_row - height)
+        };
+        let left = (anchor_left as f64 + left).floor().min(screen_width as f64 - width).max(0.0);
+        let top = (anchor_top as f64 + top).floor().min(screen_height as f64 - height).max(0.0);
+        grid.position = (left as u64, top as u64);
+        grid.visible = true;
+
+        // Moving a float doesn't raise it, only showing a new one does
+        let order = match grid.floating.as_ref().map(|floating| floating.order) {
+            Some(order) => order,
+            None => {
+                grid.invalidate();
+                *next_float_order += 1;
+                *next_float_order
+            }
+        };
+        grid.floating = Some(FloatingWindow { order, anchor_grid, focusable, popup_menu: window < 0 });
+    }
+
+    // Messages are drawn into a grid of their own which spans the screen from row down
+    fn position_message_grid(&mut self, grid_id: u64, row: u64) {
+        self.message_grid = Some(grid_id);
+        if let Some(grid) = self.grids.get_mut(&grid_id) {
+            grid.position = (0, row);
+            grid.visible = true;
+        }
+    }
+
+    fn hide_window(&mut self, grid_id: u64) {
+        if let Some(grid) = self.grids.get_mut(&grid_id) {
+            grid.visible = false;
+        }
+    }
+
+    fn destroy_grid(&mut self, grid_id: u64) {
+        // Neovim never destroys the global grid, but a misbehaving server shouldn't be able to
+        // leave the editor without a screen
+        if grid_id != GLOBAL_GRID {
+            self.grids.remove(&grid_id);
+        }
+        if self.message_grid == Some(grid_id) {
+            self.message_grid = None;
+        }
+    }
+
+    fn set_option(&mut self, gui_option: GuiOption) {
+        match gui_option {
+            GuiOption::GuiFont(font_description) => {
+                let parts: Vec<&str> = font_description.split(":").collect();
+                self.font_name = Some(parts[0].to_string());
+                for part in parts.iter().skip(1) {
+                    if part.starts_with("h") && part.len() > 1 {
+                        self.font_size = part[1..].parse::<f32>().ok();
+                    }
+                }
+            },
+            GuiOption::Pumblend(blend) => self.popup_menu_blend = blend.min(100) as u8,
+            _ => {}
+        }
+    }
+}
+
@@ -1,10 +1,24 @@
-use skulpin::skia_safe::Color4f;
+// Colour components from 0 to 1, laid out the same way as skia's Color4f so renderers can
+// convert without any rounding
+#[derive(new, PartialEq, Debug, Clone)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32
+}
+
+impl Color {
+    pub const WHITE: Color = Color { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const BLACK: Color = Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const GREY: Color = Color { r: 0.5, g: 0.5, b: 0.5, a: 1.0 };
+}
 
 #[derive(new, PartialEq, Debug, Clone)]
 pub struct Colors {
-    pub foreground: Option<Color4f>,
-    pub background: Option<Color4f>,
-    pub special: Option<Color4f>
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub special: Option<Color>
 }
 
 #[derive(new, Debug, Clone, PartialEq)]
@@ -27,7 +41,7 @@ pub struct Style {
 }
 
 impl Style {
-    pub fn foreground(&self, default_colors: &Colors) -> Color4f {
+    pub fn foreground(&self, default_colors: &Colors) -> Color {
         if self.reverse {
             self.colors.background.clone().unwrap_or(default_colors.background.clone().unwrap())
         } else {
@@ -35,7 +49,7 @@ impl Style {
         }
     }
 
-    pub fn background(&self, default_colors: &Colors) -> Color4f {
+    pub fn background(&self, default_colors: &Colors) -> Color {
         if self.reverse {
             self.colors.foreground.clone().unwrap_or(default_colors.foreground.clone().unwrap())
         } else {
@@ -43,7 +57,7 @@ impl Style {
         }
     }
 
-    pub fn special(&self, default_colors: &Colors) -> Color4f {
+    pub fn special(&self, default_colors: &Colors) -> Color {
         self.colors.special.clone().unwrap_or(default_colors.special.clone().unwrap())
     }
 }
@@ -0,0 +1,213 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use super::*;
+
+// Editor states are compared with the dumps checked in under tests/fixtures/editor. Running the
+// tests with NEOVIDE_UPDATE_FIXTURES=1 writes the dumps instead, and a missing dump fails the test
+// rather than being created.
+fn assert_matches_fixture(editor: &mut Editor, name: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/editor")
+        .join(format!("{}.dump", name));
+    let actual = editor.dump_state();
+
+    if env::var_os("NEOVIDE_UPDATE_FIXTURES").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|error| panic!(
+        "Could not read the fixture {}: {}. Run with NEOVIDE_UPDATE_FIXTURES=1 to create it",
+        path.display(), error));
+    if let Some(diff) = diff_dumps(&expected, &actual) {
+        panic!("The editor does not match {}:\n{}", path.display(), diff);
+    }
+}
+
+fn editor_with(events: Vec<RedrawEvent>) -> Editor {
+    let mut editor = Editor::new((100, 50));
+    for event in events {
+        editor.handle_redraw_event(event);
+    }
+    editor
+}
+
+// One cell per character, all without a highlight
+fn plain_cells(text: &str) -> Vec<GridLineCell> {
+    text.chars()
+        .map(|character| GridLineCell { text: character.to_string(), highlight_id: Some(0), repeat: None })
+        .collect()
+}
+
+fn plain_line(row: u64, text: &str) -> RedrawEvent {
+    RedrawEvent::GridLine { grid: GLOBAL_GRID, row, column_start: 0, cells: plain_cells(text) }
+}
+
+// Two rows of five distinct characters, dumped once so that only later changes are dirty
+fn scrollable_editor() -> Editor {
+    let mut editor = editor_with(vec![
+        RedrawEvent::Resize { grid: GLOBAL_GRID, width: 5, height: 2 },
+        plain_line(0, "abcde"),
+        plain_line(1, "fghij")
+    ]);
+    editor.dump_state();
+    editor
+}
+
+fn scroll_columns(editor: &mut Editor, columns: i64) {
+    editor.handle_redraw_event(RedrawEvent::Scroll {
+        grid: GLOBAL_GRID, top: 0, bottom: 2, left: 0, right: 5, rows: 0, columns
+    });
+}
+
+#[test]
+fn grid_lines_resolve_highlights_and_repeats() {
+    let mut red_bold = Style::new(Colors::new(Some(Color::new(1.0, 0.0, 0.0, 1.0)), None, None));
+    red_bold.bold = true;
+
+    let mut editor = editor_with(vec![
+        RedrawEvent::Resize { grid: GLOBAL_GRID, width: 6, height: 1 },
+        RedrawEvent::HighlightAttributesDefine { id: 1, style: red_bold },
+        RedrawEvent::GridLine { grid: GLOBAL_GRID, row: 0, column_start: 0, cells: vec![
+            GridLineCell { text: "a".to_string(), highlight_id: Some(1), repeat: None },
+            // Without a highlight id a cell reuses the previous cell's highlight
+            GridLineCell { text: "b".to_string(), highlight_id: None, repeat: Some(2) },
+            GridLineCell { text: " ".to_string(), highlight_id: Some(0), repeat: Some(3) }
+        ] }
+    ]);
+    assert_matches_fixture(&mut editor, "grid_line_styles");
+}
+
+// Positive columns move the region left
+#[test]
+fn scrolling_left_moves_columns_left() {
+    let mut editor = scrollable_editor();
+    scroll_columns(&mut editor, 2);
+    assert_matches_fixture(&mut editor, "scroll_left");
+}
+
+// Regression test for the region being cut short by checking rows where columns were meant
+#[test]
+fn scrolling_right_moves_columns_right() {
+    let mut editor = scrollable_editor();
+    scroll_columns(&mut editor, -2);
+    assert_matches_fixture(&mut editor, "scroll_right");
+}
+
+// A 20x10 screen with a 10x4 window grid placed at column 5, row 3
+fn window_editor() -> Editor {
+    editor_with(vec![
+        RedrawEvent::Resize { grid: GLOBAL_GRID, width: 20, height: 10 },
+        RedrawEvent::Resize { grid: 2, width: 10, height: 4 },
+        RedrawEvent::WindowPosition { grid: 2, window: 1000, start_row: 3, start_column: 5, width: 10, height: 4 }
+    ])
+}
+
+#[test]
+fn windows_are_placed_at_their_start_position() {
+    let editor = window_editor();
+    assert_eq!(editor.grids[&2].position, (5, 3));
+    assert!(editor.grids[&2].visible);
+    assert_eq!(editor.draw_order(), vec![GLOBAL_GRID, 2]);
+
+    assert_eq!(editor.grid_at((5, 3)), Some(2));
+    assert_eq!(editor.grid_at((14, 6)), Some(2));
+    assert_eq!(editor.grid_at((4, 3)), Some(GLOBAL_GRID));
+    assert_eq!(editor.grid_at((15, 3)), Some(GLOBAL_GRID));
+    assert_eq!(editor.grid_at((5, 7)), Some(GLOBAL_GRID));
+    assert_eq!(editor.grid_at((20, 0)), None);
+}
+
+#[test]
+fn hidden_windows_keep_their_grid_but_not_the_mouse() {
+    let mut editor = window_editor();
+    editor.handle_redraw_event(RedrawEvent::WindowHide { grid: 2 });
+    assert!(!editor.grids[&2].visible);
+    assert_eq!(editor.grid_at((5, 3)), Some(GLOBAL_GRID));
+
+    editor.handle_redraw_event(RedrawEvent::WindowPosition { grid: 2, window: 1000, start_row: 3, start_column: 5, width: 10, height: 4 });
+    assert_eq!(editor.grid_at((5, 3)), Some(2));
+
+    editor.handle_redraw_event(RedrawEvent::WindowClose { grid: 2 });
+    assert!(editor.grids.contains_key(&2));
+    assert_eq!(editor.grid_at((5, 3)), Some(GLOBAL_GRID));
+}
+
+#[test]
+fn destroyed_grids_are_removed_except_the_global_grid() {
+    let mut editor = window_editor();
+    editor.handle_redraw_event(RedrawEvent::Resize { grid: 3, width: 20, height: 2 });
+    editor.handle_redraw_event(RedrawEvent::MessageSetPosition { grid: 3, row: 8, scrolled: false, separator_character: String::new() });
+    assert_eq!(editor.message_grid, Some(3));
+
+    editor.handle_redraw_event(RedrawEvent::Destroy { grid: 2 });
+    editor.handle_redraw_event(RedrawEvent::Destroy { grid: 3 });
+    editor.handle_redraw_event(RedrawEvent::Destroy { grid: GLOBAL_GRID });
+    assert_eq!(editor.grids.keys().collect::<Vec<_>>(), vec![&GLOBAL_GRID]);
+    assert_eq!(editor.message_grid, None);
+    assert_eq!(editor.grid_at((5, 3)), Some(GLOBAL_GRID));
+}
+
+fn float_position(grid: u64, window: i64, anchor: WindowAnchor, anchor_grid: u64, (anchor_column, anchor_row): (f64, f64)) -> RedrawEvent {
+    RedrawEvent::WindowFloatPosition { grid, window, anchor, anchor_grid, anchor_row, anchor_column, focusable: true }
+}
+
+// Places a 4x2 float on window_editor's window grid and returns where it ends up
+fn float_placed_at(anchor: WindowAnchor, anchor_grid: u64, anchor_position: (f64, f64)) -> (u64, u64) {
+    let mut editor = window_editor();
+    editor.handle_redraw_event(RedrawEvent::Resize { grid: 4, width: 4, height: 2 });
+    editor.handle_redraw_event(float_position(4, 1001, anchor, anchor_grid, anchor_position));
+    assert!(editor.grids[&4].visible);
+    editor.grids[&4].position
+}
+
+// The anchor is relative to the anchor grid, which sits at column 5, row 3, and fractional
+// cells round down
+#[test]
+fn floats_are_placed_by_the_corner_they_are_anchored_with() {
+    assert_eq!(float_placed_at(WindowAnchor::NorthWest, 2, (2.5, 1.5)), (7, 4));
+    assert_eq!(float_placed_at(WindowAnchor::NorthEast, 2, (2.5, 1.5)), (3, 4));
+    assert_eq!(float_placed_at(WindowAnchor::SouthWest, 2, (2.5, 1.5)), (7, 2));
+    assert_eq!(float_placed_at(WindowAnchor::SouthEast, 2, (2.5, 1.5)), (3, 2));
+}
+
+#[test]
+fn floats_are_kept_on_the_screen() {
+    assert_eq!(float_placed_at(WindowAnchor::NorthWest, GLOBAL_GRID, (18.0, 9.0)), (16, 8));
+    assert_eq!(float_placed_at(WindowAnchor::SouthEast, GLOBAL_GRID, (1.0, 1.0)), (0, 0));
+    assert_eq!(float_placed_at(WindowAnchor::NorthWest, 2, (14.0, 0.0)), (16, 3));
+}
+
+// Floats stack in the order they were first shown, moving one doesn't raise it, and the
+// message grid stays on top of them all
+#[test]
+fn floats_stack_in_the_order_they_were_shown() {
+    let mut editor = window_editor();
+    editor.handle_redraw_event(RedrawEvent::Resize { grid: 4, width: 4, height: 2 });
+    editor.handle_redraw_event(RedrawEvent::Resize { grid: 5, width: 4, height: 2 });
+    editor.handle_redraw_event(RedrawEvent::Resize { grid: 6, width: 20, height: 1 });
+    editor.handle_redraw_event(float_position(4, 1001, WindowAnchor::NorthWest, 2, (0.0, 0.0)));
+    editor.handle_redraw_event(float_position(5, -1, WindowAnchor::NorthWest, 2, (1.0, 0.0)));
+    editor.handle_redraw_event(RedrawEvent::MessageSetPosition { grid: 6, row: 3, scrolled: false, separator_character: String::new() });
+    assert_eq!(editor.draw_order(), vec![GLOBAL_GRID, 2, 4, 5, 6]);
+    assert!(editor.grids[&5].floating.as_ref().unwrap().popup_menu);
+    assert!(!editor.grids[&4].floating.as_ref().unwrap().popup_menu);
+
+    // The message grid covers both floats, and the popup menu covers the float under it
+    assert_eq!(editor.grid_at((6, 3)), Some(6));
+    assert_eq!(editor.grid_at((6, 4)), Some(5));
+    assert_eq!(editor.grid_at((5, 4)), Some(4));
+
+    editor.handle_redraw_event(float_position(4, 1001, WindowAnchor::NorthWest, 2, (1.0, 0.0)));
+    assert_eq!(editor.draw_order(), vec![GLOBAL_GRID, 2, 4, 5, 6]);
+    assert_eq!(editor.grid_at((6, 4)), Some(5));
+
+    // A float which turns into a normal window and back is shown again on top
+    editor.handle_redraw_event(RedrawEvent::WindowPosition { grid: 4, window: 1001, start_row: 3, start_column: 5, width: 4, height: 2 });
+    assert_eq!(editor.draw_order(), vec![GLOBAL_GRID, 2, 4, 5, 6]);
+    editor.handle_redraw_event(float_position(4, 1001, WindowAnchor::NorthWest, 2, (1.0, 0.0)));
+    assert_eq!(editor.draw_order(), vec![GLOBAL_GRID, 2, 5, 4, 6]);
+    assert_eq!(editor.grid_at((6, 4)), Some(4));
+}
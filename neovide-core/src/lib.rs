@@ -0,0 +1,14 @@
+// The parts of Neovide which don't need a window: the neovim session driver, the redraw event
+// parser and the grid model those events build. The GUI renders on top of this crate, and other
+// frontends or test harnesses can drive it without skia or winit.
+
+pub mod bridge;
+pub mod editor;
+pub mod clipboard;
+pub mod settings;
+pub mod session;
+pub mod redraw_scheduler;
+
+#[macro_use] extern crate derive_new;
+
+pub const INITIAL_DIMENSIONS: (u64, u64) = (100, 50);
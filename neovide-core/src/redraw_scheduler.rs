@@ -2,25 +2,27 @@ use std::sync::Mutex;
 use std::sync::atomic::{AtomicU16, Ordering};
 use std::time::Instant;
 
-lazy_static! {
-    pub static ref REDRAW_SCHEDULER: RedrawScheduler = RedrawScheduler::new();
-}
-
-const BUFFER_FRAMES: u16 = 60;
-
+// Keeps its own copy of the buffer_frames setting so that queueing a frame never waits on the
+// settings lock
 pub struct RedrawScheduler {
     frames_queued: AtomicU16,
+    buffer_frames: AtomicU16,
     scheduled_frame: Mutex<Option<Instant>>
 }
 
 impl RedrawScheduler {
-    pub fn new() -> RedrawScheduler {
+    pub fn new(buffer_frames: u64) -> RedrawScheduler {
         RedrawScheduler { 
             frames_queued: AtomicU16::new(1),
+            buffer_frames: AtomicU16::new(buffer_frames as u16),
             scheduled_frame: Mutex::new(None)
         }
     }
 
+    pub fn set_buffer_frames(&self, buffer_frames: u64) {
+        self.buffer_frames.store(buffer_frames as u16, Ordering::Relaxed);
+    }
+
     pub fn schedule(&self, new_scheduled: Instant) {
         let mut scheduled_frame = self.scheduled_frame.lock().unwrap();
         if let Some(previous_scheduled) = scheduled_frame.clone() {
@@ -33,7 +35,7 @@ impl RedrawScheduler {
     }
 
     pub fn queue_next_frame(&self) {
-        self.frames_queued.store(BUFFER_FRAMES, Ordering::Relaxed);
+        self.frames_queued.store(self.buffer_frames.load(Ordering::Relaxed), Ordering::Relaxed);
     }
 
     pub fn should_draw(&self) -> bool {
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex};
+
+use crate::bridge::{Bridge, Connection, Recorder, SessionStatus, UiExtensions};
+use crate::clipboard::{ClipboardBackend, ClipboardSetting, MemoryClipboard};
+use crate::editor::{Editor, GLOBAL_GRID};
+use crate::redraw_scheduler::RedrawScheduler;
+use crate::settings::Settings;
+use crate::INITIAL_DIMENSIONS;
+
+// Everything a session needs to know before it starts. The GUI fills this in from the command
+// line and config.toml.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionOptions {
+    pub initial_dimensions: (u64, u64),
+    pub neovim_bin: String,
+    pub wrapper: Vec<String>,
+    pub ui_extensions: Vec<String>,
+    pub clipboard: ClipboardSetting
+}
+
+impl Default for SessionOptions {
+    fn default() -> SessionOptions {
+        SessionOptions {
+            initial_dimensions: INITIAL_DIMENSIONS,
+            neovim_bin: "nvim".to_string(),
+            wrapper: Vec::new(),
+            ui_extensions: Vec::new(),
+            clipboard: ClipboardSetting::default()
+        }
+    }
+}
+
+// The state one neovim session shares between the bridge, the renderer and the window loop.
+// Cloning it only clones the handles.
+#[derive(Clone)]
+pub struct SessionContext {
+    pub options: Arc<SessionOptions>,
+    pub editor: Arc<Mutex<Editor>>,
+    pub settings: Arc<Mutex<Settings>>,
+    pub redraw_scheduler: Arc<RedrawScheduler>,
+    pub status: Arc<Mutex<SessionStatus>>,
+    pub ui_extensions: Arc<Mutex<UiExtensions>>,
+    pub clipboard: Arc<Mutex<Box<dyn ClipboardBackend>>>,
+    pub(crate) recorder: Option<Arc<Mutex<Recorder>>>
+}
+
+impl SessionContext {
+    // Without a frontend the system clipboard setting falls back to a clipboard in memory
+    pub fn new(options: SessionOptions) -> SessionContext {
+        let clipboard = options.clipboard.create_backend()
+            .unwrap_or_else(|| Box::new(MemoryClipboard::default()));
+        SessionContext::with_frontend(options, clipboard, None)
+    }
+
+    // The frontend supplies the clipboard backend, which is the system clipboard unless the
+    // options pick another one. It also opens the recording itself so that it can report a bad
+    // path before the session starts.
+    pub fn with_frontend(options: SessionOptions, clipboard: Box<dyn ClipboardBackend>, recorder: Option<Recorder>) -> SessionContext {
+        let settings = Arc::new(Mutex::new(Settings::default()));
+        let recorder = recorder.map(|recorder| Arc::new(Mutex::new(recorder)));
+
+        SessionContext {
+            editor: Arc::new(Mutex::new(Editor::new(options.initial_dimensions))),
+            redraw_scheduler: Arc::new(RedrawScheduler::new(Settings::default().buffer_frames)),
+            settings,
+            status: Arc::new(Mutex::new(SessionStatus::Starting)),
+            ui_extensions: Arc::new(Mutex::new(UiExtensions::default())),
+            clipboard: Arc::new(Mutex::new(clipboard)),
+            recorder,
+            options: Arc::new(options)
+        }
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn set_status(&self, new_status: SessionStatus) {
+        *self.status.lock().unwrap() = new_status;
+        self.redraw_scheduler.queue_next_frame();
+    }
+
+    pub fn ui_extensions(&self) -> UiExtensions {
+        self.ui_extensions.lock().unwrap().clone()
+    }
+
+    pub fn settings(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    // Neovim wants mouse input on grid 0 unless ext_multigrid is on, in which case the position
+    // has to be relative to the grid it is sent to. Positions are (row, column) like
+    // nvim_input_mouse takes them, and without a grid the one under the mouse is used.
+    pub fn mouse_target(&self, (row, column): (i64, i64), grid: Option<u64>) -> (u64, (i64, i64)) {
+        if !self.ui_extensions().multigrid {
+            return (0, (row, column));
+        }
+
+        let editor = self.editor.lock().unwrap();
+        let grid_id = grid
+            .or_else(|| editor.grid_at((column as u64, row as u64)))
+            .unwrap_or(GLOBAL_GRID);
+        let (left, top) = editor.grids.get(&grid_id)
+            .map(|grid| grid.position)
+            .unwrap_or((0, 0));
+        (grid_id, ((row - top as i64).max(0), (column - left as i64).max(0)))
+    }
+}
+
+// A neovim session together with the bridge which drives it
+pub struct Session {
+    pub context: SessionContext,
+    pub bridge: Bridge
+}
+
+impl Session {
+    pub fn start(connection: Connection, options: SessionOptions, clipboard: Box<dyn ClipboardBackend>, recorder: Option<Recorder>) -> Session {
+        let context = SessionContext::with_frontend(options, clipboard, recorder);
+        let bridge = Bridge::new(connection, context.clone());
+        Session { context, bridge }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::{RedrawEvent, WindowAnchor};
+
+    // A 20x10 screen with a 10x4 window at column 5, row 3 and a 2x2 float over its top left
+    // corner, with ext_multigrid on
+    fn multigrid_context() -> SessionContext {
+        let context = SessionContext::new(SessionOptions::default());
+        context.ui_extensions.lock().unwrap().multigrid = true;
+        let mut editor = context.editor.lock().unwrap();
+        for event in vec![
+            RedrawEvent::Resize { grid: GLOBAL_GRID, width: 20, height: 10 },
+            RedrawEvent::Resize { grid: 2, width: 10, height: 4 },
+            RedrawEvent::WindowPosition { grid: 2, window: 1000, start_row: 3, start_column: 5, width: 10, height: 4 },
+            RedrawEvent::Resize { grid: 3, width: 2, height: 2 },
+            RedrawEvent::WindowFloatPosition {
+                grid: 3, window: 1001, anchor: WindowAnchor::NorthWest, anchor_grid: 2,
+                anchor_row: 0.0, anchor_column: 0.0, focusable: true
+            }
+        ] {
+            editor.handle_redraw_event(event);
+        }
+        drop(editor);
+        context
+    }
+
+    #[test]
+    fn mouse_goes_to_grid_zero_without_multigrid() {
+        let context = multigrid_context();
+        context.ui_extensions.lock().unwrap().multigrid = false;
+        assert_eq!(context.mouse_target((4, 7), None), (0, (4, 7)));
+    }
+
+    #[test]
+    fn mouse_goes_to_the_topmost_visible_grid() {
+        let context = multigrid_context();
+        assert_eq!(context.mouse_target((3, 5), None), (3, (0, 0)));
+        assert_eq!(context.mouse_target((4, 7), None), (2, (1, 2)));
+        assert_eq!(context.mouse_target((2, 5), None), (GLOBAL_GRID, (2, 5)));
+
+        context.editor.lock().unwrap().handle_redraw_event(RedrawEvent::WindowHide { grid: 3 });
+        assert_eq!(context.mouse_target((3, 5), None), (2, (0, 0)));
+    }
+
+    // Drags stay on the grid they started on, with positions outside of it clamped to its edge
+    #[test]
+    fn mouse_positions_are_relative_to_a_given_grid() {
+        let context = multigrid_context();
+        assert_eq!(context.mouse_target((1, 2), Some(2)), (2, (0, 0)));
+        assert_eq!(context.mouse_target((5, 9), Some(2)), (2, (2, 4)));
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use rmpv::Value;
+
+use crate::editor::Color;
+
+#[derive(Debug, Clone)]
+pub enum SettingError {
+    UnknownSetting(String),
+    InvalidValue { name: String, expected: String, value: Value }
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingError::UnknownSetting(name) => write!(f, "unknown setting {}", name),
+            SettingError::InvalidValue { name, expected, value } =>
+                write!(f, "{} expects {} but received {}", name, expected, value)
+        }
+    }
+}
+
+fn invalid_value(name: &str, expected: String, value: &Value) -> SettingError {
+    SettingError::InvalidValue { name: name.to_string(), expected, value: value.clone() }
+}
+
+// Vim script has no real booleans, so 0 and 1 are accepted alongside v:true and v:false
+fn parse_bool_setting(name: &str, value: &Value) -> Result<bool, SettingError> {
+    match value {
+        Value::Boolean(value) => Ok(*value),
+        Value::Integer(_) => match value.as_u64() {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => Err(invalid_value(name, "a boolean".to_string(), value))
+        },
+        _ => Err(invalid_value(name, "a boolean".to_string(), value))
+    }
+}
+
+fn parse_integer_setting(name: &str, value: &Value, range: RangeInclusive<u64>) -> Result<u64, SettingError> {
+    value.as_u64()
+        .filter(|value| range.contains(value))
+        .ok_or_else(|| invalid_value(name, format!("an integer from {} to {}", range.start(), range.end()), value))
+}
+
+fn parse_float_setting(name: &str, value: &Value, range: RangeInclusive<f32>) -> Result<f32, SettingError> {
+    value.as_f64()
+        .or_else(|| value.as_i64().map(|value| value as f64))
+        .map(|value| value as f32)
+        .filter(|value| range.contains(value))
+        .ok_or_else(|| invalid_value(name, format!("a number from {} to {}", range.start(), range.end()), value))
+}
+
+// Colours are written the same way as in highlight groups, as #rrggbb, with an optional alpha
+// byte on the end
+fn parse_color_setting(name: &str, value: &Value) -> Result<Color, SettingError> {
+    let invalid = || invalid_value(name, "a colour such as #000000 or #00000080".to_string(), value);
+    let hex = value.as_str()
+        .filter(|text| text.starts_with('#') && (text.len() == 7 || text.len() == 9))
+        .ok_or_else(invalid)?;
+    let mut components = Vec::new();
+    for index in (1..hex.len()).step_by(2) {
+        let component = hex.get(index..index + 2)
+            .and_then(|component| u8::from_str_radix(component, 16).ok())
+            .ok_or_else(invalid)?;
+        components.push(component as f32 / 255.0);
+    }
+    Ok(Color::new(components[0], components[1], components[2], components.get(3).cloned().unwrap_or(1.0)))
+}
+
+// Declares every setting once, as its name, type, default and the parser which validates new
+// values. The names double as the g:neovide_ variable suffixes.
+macro_rules! define_settings {
+    ($($name:ident: $type:ty = $default:expr, $parse:ident $(($range:expr))?;)*) => {
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct Settings {
+            $(pub $name: $type),*
+        }
+
+        impl Default for Settings {
+            fn default() -> Settings {
+                Settings {
+                    $($name: $default),*
+                }
+            }
+        }
+
+        pub const SETTING_NAMES: &[&str] = &[$(stringify!($name)),*];
+
+        impl Settings {
+            pub fn set(&mut self, name: &str, value: &Value) -> Result<(), SettingError> {
+                match name {
+                    $(stringify!($name) => self.$name = $parse(name, value $(, $range)?)?,)*
+                    _ => return Err(SettingError::UnknownSetting(name.to_string()))
+                };
+                Ok(())
+            }
+
+            pub fn reset(&mut self, name: &str) -> Result<(), SettingError> {
+                let defaults = Settings::default();
+                match name {
+                    $(stringify!($name) => self.$name = defaults.$name,)*
+                    _ => return Err(SettingError::UnknownSetting(name.to_string()))
+                };
+                Ok(())
+            }
+        }
+    }
+}
+
+define_settings! {
+    cursor_animation: bool = true, parse_bool_setting;
+    cursor_motion_percentage: f32 = 0.7, parse_float_setting(0.3..=1.0);
+    refresh_rate: u64 = 60, parse_integer_setting(1..=240);
+    buffer_frames: u64 = 60, parse_integer_setting(1..=600);
+    fullscreen: bool = false, parse_bool_setting;
+    scale: f32 = 1.0, parse_float_setting(0.25..=4.0);
+    padding: u64 = 0, parse_integer_setting(0..=200);
+    font_size: f32 = 14.0, parse_float_setting(1.0..=200.0);
+    floating_shadow_radius: f32 = 0.0, parse_float_setting(0.0..=100.0);
+    floating_shadow_offset_x: f32 = 0.0, parse_float_setting(-100.0..=100.0);
+    floating_shadow_offset_y: f32 = 4.0, parse_float_setting(-100.0..=100.0);
+    floating_shadow_color: Color = Color::new(0.0, 0.0, 0.0, 0.5), parse_color_setting;
+    floating_corner_radius: f32 = 0.0, parse_float_setting(0.0..=50.0);
+}
@@ -0,0 +1,478 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use neovide_core::bridge::{Connection, Recorder, UI_EXTENSION_NAMES};
+use neovide_core::clipboard::ClipboardSetting;
+use neovide_core::session::SessionOptions;
+use neovide_core::INITIAL_DIMENSIONS;
+
+use crate::config::{Config, RendererBackend};
+
+lazy_static! {
+    pub static ref COMMAND_LINE_SETTINGS: CommandLineSettings = CommandLineSettings::from_process_args();
+}
+
+const DEFAULT_PASTE_KEY: &str = "<C-S-v>";
+
+// The ui extensions this frontend can draw. Neovim stops drawing whatever an extension takes
+// over, so turning on cmdline, popupmenu, messages or tabline would make them vanish.
+pub const SUPPORTED_UI_EXTENSIONS: &[&str] = &["multigrid", "hlstate"];
+
+const USAGE: &str = "\
+USAGE:
+    neovide [OPTIONS] [FILES]... [-- <NEOVIM ARGS>...]
+
+OPTIONS:
+        --help                   Print this message and exit
+        --version                Print the version and exit
+        --print-config           Print the effective configuration as TOML and exit
+        --config <FILE>          Read the configuration from FILE instead of
+                                 $XDG_CONFIG_HOME/neovide/config.toml
+        --geometry <COLSxROWS>   Initial size of the window in grid cells
+        --maximized              Start with the window maximized
+        --no-maximized           Start with the window unmaximized, even when config.toml says otherwise
+        --renderer-backend <GPU> Prefer the integrated or the discrete gpu. Defaults to integrated
+        --log <FILE>             Write log output to FILE
+        --server [ADDRESS]       Attach to a running neovim at a host:port address or socket path,
+                                 defaults to $NVIM_LISTEN_ADDRESS
+        --neovim-bin <PATH>      Neovim executable to launch, defaults to $NEOVIM_BIN or nvim
+        --wrapper <COMMAND>      Launch neovim through COMMAND, such as \"ssh host\" or \"docker exec -i box\"
+        --multigrid              Enable the experimental multigrid extension, same as --ext multigrid
+        --ext <NAMES>            Comma separated ui extensions to request from neovim: multigrid and hlstate
+        --clipboard <BACKEND>    Clipboard offered to neovim when it has no clipboard tool of its own: system,
+                                 memory, file:<DIR> or none. Defaults to system
+        --paste-key <KEYS>       Shortcut that pastes the clipboard through nvim_paste, such as <D-v>. Defaults
+                                 to <C-S-v>, and none turns it off
+        --record <FILE>          Record everything neovim sends to FILE for a bug report
+        --replay <FILE>          Play back a recording made with --record instead of starting neovim
+        --replay-fast            Play the recording back as fast as possible rather than in real time
+        --headless <DIR>         Render the --replay recording without a window or gpu and write
+                                 DIR/final.png
+        --snapshot-every-flush   With --headless, also write DIR/frame-NNNNN.png for every flushed frame
+        --check-grid <FILE>      Apply the --replay recording to the editor and compare the resulting grid,
+                                 styles, dirty cells and draw commands with the dump in FILE
+        --update-grid            With --check-grid, write the dump to FILE instead of comparing
+        --nofork                 Do not detach from the launching terminal. Only Linux and the BSDs detach
+
+Unrecognized arguments and everything after -- are passed to neovim. Flags override config.toml.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandLineError {
+    MissingValue(String),
+    InvalidGeometry(String),
+    UnknownExtension(String),
+    UnsupportedExtension(String),
+    UnknownClipboard(String),
+    UnknownRendererBackend(String),
+    CannotRecord(PathBuf, String),
+    HeadlessWithoutReplay,
+    CheckGridWithoutReplay
+}
+
+impl fmt::Display for CommandLineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandLineError::MissingValue(flag) => write!(f, "{} requires a value", flag),
+            CommandLineError::InvalidGeometry(value) => write!(f, "invalid geometry '{}', expected COLSxROWS such as 100x50", value),
+            CommandLineError::UnknownExtension(name) => write!(f, "unknown ui extension '{}', expected one of {}", name, SUPPORTED_UI_EXTENSIONS.join(", ")),
+            CommandLineError::UnsupportedExtension(name) => write!(
+                f, "neovide can't draw the {} ui extension yet, and neovim would stop drawing it too. Supported extensions are {}",
+                name, SUPPORTED_UI_EXTENSIONS.join(", ")),
+            CommandLineError::UnknownClipboard(value) => write!(f, "unknown clipboard '{}', expected system, memory, file:<DIR> or none", value),
+            CommandLineError::UnknownRendererBackend(value) => write!(f, "unknown renderer backend '{}', expected integrated or discrete", value),
+            CommandLineError::CannotRecord(path, error) => write!(f, "could not create the recording {}: {}", path.display(), error),
+            CommandLineError::HeadlessWithoutReplay => write!(f, "--headless renders a recording and needs --replay <FILE>"),
+            CommandLineError::CheckGridWithoutReplay => write!(f, "--check-grid checks a recording and needs --replay <FILE>")
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandLineRequest {
+    Run(CommandLineSettings),
+    PrintConfig(CommandLineSettings),
+    Help,
+    Version
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandLineSettings {
+    pub config_file: Option<PathBuf>,
+    pub geometry: Option<(u64, u64)>,
+    pub maximized: Option<bool>,
+    pub renderer_backend: Option<RendererBackend>,
+    pub font_fallbacks: Vec<String>,
+    pub log_file: Option<PathBuf>,
+    pub server: Option<String>,
+    pub neovim_bin: Option<String>,
+    pub wrapper: Vec<String>,
+    pub ui_extensions: Vec<String>,
+    pub clipboard: ClipboardSetting,
+    pub paste_key: Option<String>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_fast: bool,
+    pub headless: Option<PathBuf>,
+    pub snapshot_every_flush: bool,
+    pub check_grid: Option<PathBuf>,
+    pub update_grid: bool,
+    pub no_fork: bool,
+    pub neovim_args: Vec<String>
+}
+
+pub fn parse_geometry(value: &str) -> Result<(u64, u64), CommandLineError> {
+    let parts: Vec<&str> = value.split('x').collect();
+    if let [columns, rows] = parts.as_slice() {
+        match (columns.parse::<u64>(), rows.parse::<u64>()) {
+            (Ok(columns), Ok(rows)) if columns > 0 && rows > 0 => return Ok((columns, rows)),
+            _ => {}
+        }
+    }
+    Err(CommandLineError::InvalidGeometry(value.to_string()))
+}
+
+// Splits a command into its argv, honouring single quotes, double quotes and backslash escapes
+// the way a posix shell would.
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quote = None;
+    let mut characters = command.chars();
+
+    while let Some(character) = characters.next() {
+        match (quote, character) {
+            (Some(open), character) if character == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(escaped) = characters.next() {
+                    current.push(escaped);
+                }
+                in_argument = true;
+            },
+            (Some(_), character) => current.push(character),
+            (None, '\'') | (None, '"') => {
+                quote = Some(character);
+                in_argument = true;
+            },
+            (None, character) if character.is_whitespace() => {
+                if in_argument {
+                    arguments.push(std::mem::replace(&mut current, String::new()));
+                    in_argument = false;
+                }
+            },
+            (None, character) => {
+                current.push(character);
+                in_argument = true;
+            }
+        }
+    }
+
+    if in_argument {
+        arguments.push(current);
+    }
+    arguments
+}
+
+// listen_address is the value of NVIM_LISTEN_ADDRESS, which a bare --server connects to
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I, listen_address: Option<String>) -> Result<CommandLineRequest, CommandLineError> {
+    let mut settings = CommandLineSettings::default();
+    let mut print_config = false;
+    let mut args = args.into_iter().peekable();
+
+    while let Some(arg) = args.next() {
+        // Both "--flag value" and "--flag=value" are accepted
+        let (flag, inline_value) = match arg.find('=') {
+            Some(index) if arg.starts_with("--") => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+            _ => (arg.clone(), None)
+        };
+        let mut value = || inline_value.clone()
+            .or_else(|| args.next())
+            .ok_or_else(|| CommandLineError::MissingValue(flag.clone()));
+
+        match flag.as_str() {
+            "--help" => return Ok(CommandLineRequest::Help),
+            "--version" => return Ok(CommandLineRequest::Version),
+            "--print-config" => print_config = true,
+            "--config" => settings.config_file = Some(PathBuf::from(value()?)),
+            "--geometry" => settings.geometry = Some(parse_geometry(&value()?)?),
+            "--maximized" => settings.maximized = Some(true),
+            "--no-maximized" => settings.maximized = Some(false),
+            "--renderer-backend" => {
+                let value = value()?;
+                settings.renderer_backend = Some(RendererBackend::parse(&value)
+                    .ok_or_else(|| CommandLineError::UnknownRendererBackend(value))?);
+            },
+            "--log" => settings.log_file = Some(PathBuf::from(value()?)),
+            "--server" => {
+                // The address is optional, so a flag after --server is left to be parsed as one
+                let address = match inline_value.clone() {
+                    Some(address) => Some(address),
+                    None => match args.peek() {
+                        Some(next) if !next.starts_with('-') => args.next(),
+                        _ => listen_address.clone()
+                    }
+                };
+                settings.server = Some(address.ok_or_else(|| CommandLineError::MissingValue(flag.clone()))?);
+            },
+            "--neovim-bin" => settings.neovim_bin = Some(value()?),
+            "--wrapper" => settings.wrapper = split_command(&value()?),
+            "--multigrid" => settings.ui_extensions.push("multigrid".to_string()),
+            "--ext" => {
+                for name in value()?.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                    if !UI_EXTENSION_NAMES.contains(&name) {
+                        return Err(CommandLineError::UnknownExtension(name.to_string()));
+                    }
+                    if !SUPPORTED_UI_EXTENSIONS.contains(&name) {
+                        return Err(CommandLineError::UnsupportedExtension(name.to_string()));
+                    }
+                    settings.ui_extensions.push(name.to_string());
+                }
+            },
+            "--clipboard" => {
+                let value = value()?;
+                settings.clipboard = ClipboardSetting::parse(&value)
+                    .ok_or_else(|| CommandLineError::UnknownClipboard(value))?;
+            },
+            "--paste-key" => settings.paste_key = Some(value()?),
+            "--record" => settings.record = Some(PathBuf::from(value()?)),
+            "--replay" => settings.replay = Some(PathBuf::from(value()?)),
+            "--replay-fast" => settings.replay_fast = true,
+            "--headless" => settings.headless = Some(PathBuf::from(value()?)),
+            "--snapshot-every-flush" => settings.snapshot_every_flush = true,
+            "--check-grid" => settings.check_grid = Some(PathBuf::from(value()?)),
+            "--update-grid" => settings.update_grid = true,
+            "--nofork" => settings.no_fork = true,
+            "--" => {
+                settings.neovim_args.extend(args);
+                break;
+            },
+            _ => settings.neovim_args.push(arg)
+        }
+    }
+
+    if settings.headless.is_some() && settings.replay.is_none() {
+        return Err(CommandLineError::HeadlessWithoutReplay);
+    }
+    if settings.check_grid.is_some() && settings.replay.is_none() {
+        return Err(CommandLineError::CheckGridWithoutReplay);
+    }
+
+    if print_config {
+        Ok(CommandLineRequest::PrintConfig(settings))
+    } else {
+        Ok(CommandLineRequest::Run(settings))
+    }
+}
+
+impl CommandLineSettings {
+    fn from_process_args() -> CommandLineSettings {
+        match parse_args(std::env::args().skip(1), std::env::var("NVIM_LISTEN_ADDRESS").ok()) {
+            Ok(CommandLineRequest::Run(settings)) => settings.with_config_file(),
+            Ok(CommandLineRequest::PrintConfig(settings)) => {
+                print!("{}", Config::from_settings(&settings.with_config_file()).to_toml());
+                std::process::exit(0);
+            },
+            Ok(CommandLineRequest::Help) => {
+                println!("Neovide {}\n\n{}", env!("CARGO_PKG_VERSION"), USAGE);
+                std::process::exit(0);
+            },
+            Ok(CommandLineRequest::Version) => {
+                println!("Neovide {}", env!("CARGO_PKG_VERSION"));
+                std::process::exit(0);
+            },
+            Err(error) => {
+                eprintln!("error: {}\n\n{}", error, USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Fills in everything the command line left unset from config.toml
+    fn with_config_file(self) -> CommandLineSettings {
+        match Config::load(self.config_file.as_deref()) {
+            Ok(config) => self.with_config(config, std::env::var("NEOVIM_BIN").ok()),
+            Err(error) => {
+                eprintln!("error: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Takes NEOVIM_BIN as an argument so that every precedence rule lives here
+    pub fn with_config(mut self, config: Config, neovim_bin_variable: Option<String>) -> CommandLineSettings {
+        // Config::parse has already validated the geometry
+        self.geometry = self.geometry
+            .or_else(|| config.geometry.as_deref().and_then(|geometry| parse_geometry(geometry).ok()));
+        // NEOVIM_BIN is set for one launch, so it beats the file but not the flag
+        self.neovim_bin = self.neovim_bin
+            .or(neovim_bin_variable)
+            .or(config.neovim_bin);
+        if self.wrapper.is_empty() {
+            self.wrapper = config.wrapper.unwrap_or_default();
+        }
+        self.maximized = self.maximized.or(config.maximized);
+        self.renderer_backend = self.renderer_backend.or(config.renderer_backend);
+        self.font_fallbacks = config.font.fallbacks;
+        self
+    }
+
+    pub fn neovim_binary(&self) -> String {
+        self.neovim_bin.clone().unwrap_or_else(|| "nvim".to_string())
+    }
+
+    pub fn maximized(&self) -> bool {
+        self.maximized.unwrap_or(false)
+    }
+
+    pub fn renderer_backend(&self) -> RendererBackend {
+        self.renderer_backend.unwrap_or_default()
+    }
+
+    pub fn initial_dimensions(&self) -> (u64, u64) {
+        self.geometry.unwrap_or(INITIAL_DIMENSIONS)
+    }
+
+    pub fn connection(&self) -> Connection {
+        if let Some(path) = &self.replay {
+            return Connection::Replay { path: path.clone(), realtime: !self.replay_fast };
+        }
+
+        match &self.server {
+            Some(address) => Connection::from_address(address.clone()),
+            None => Connection::Embedded(self.neovim_args.clone())
+        }
+    }
+
+    pub fn session_options(&self) -> SessionOptions {
+        SessionOptions {
+            initial_dimensions: self.initial_dimensions(),
+            neovim_bin: self.neovim_binary(),
+            wrapper: self.wrapper.clone(),
+            ui_extensions: self.ui_extensions.clone(),
+            clipboard: self.clipboard.clone()
+        }
+    }
+
+    // Opens the --record file up front so that an unwritable path is reported like any other bad
+    // argument
+    pub fn recorder(&self) -> Result<Option<Recorder>, CommandLineError> {
+        self.record.as_ref()
+            .map(|path| Recorder::create(path)
+                .map_err(|error| CommandLineError::CannotRecord(path.clone(), error.to_string())))
+            .transpose()
+    }
+
+    pub fn paste_keybinding(&self) -> Option<&str> {
+        match self.paste_key.as_deref() {
+            Some("none") => None,
+            Some(keybinding) => Some(keybinding),
+            None => Some(DEFAULT_PASTE_KEY)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CommandLineRequest, CommandLineError> {
+        parse_args(args.iter().map(|arg| arg.to_string()), None)
+    }
+
+    fn settings(args: &[&str]) -> CommandLineSettings {
+        match parse(args) {
+            Ok(CommandLineRequest::Run(settings)) => settings,
+            other => panic!("expected settings but got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn help_and_version_stop_parsing() {
+        assert_eq!(parse(&["--help", "--bogus"]), Ok(CommandLineRequest::Help));
+        assert_eq!(parse(&["--version"]), Ok(CommandLineRequest::Version));
+    }
+
+    #[test]
+    fn flags_take_separate_or_inline_values() {
+        let settings = settings(&[
+            "--geometry", "120x40", "--log=neovide.log", "--neovim-bin", "/opt/nvim",
+            "--multigrid", "--nofork", "--maximized", "--renderer-backend", "discrete"
+        ]);
+        assert_eq!(settings.geometry, Some((120, 40)));
+        assert_eq!(settings.log_file, Some(PathBuf::from("neovide.log")));
+        assert_eq!(settings.neovim_bin, Some("/opt/nvim".to_string()));
+        assert_eq!(settings.ui_extensions, vec!["multigrid".to_string()]);
+        assert!(settings.no_fork);
+        assert_eq!(settings.maximized, Some(true));
+        assert_eq!(settings.renderer_backend, Some(RendererBackend::Discrete));
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        assert_eq!(parse(&["--geometry", "wide"]), Err(CommandLineError::InvalidGeometry("wide".to_string())));
+        assert_eq!(parse(&["--log"]), Err(CommandLineError::MissingValue("--log".to_string())));
+        assert_eq!(parse(&["--headless", "out"]), Err(CommandLineError::HeadlessWithoutReplay));
+    }
+
+    #[test]
+    fn only_extensions_neovide_draws_can_be_requested() {
+        assert_eq!(settings(&["--ext", "multigrid, hlstate"]).ui_extensions, vec!["multigrid", "hlstate"]);
+        assert_eq!(parse(&["--ext", "cmdline"]), Err(CommandLineError::UnsupportedExtension("cmdline".to_string())));
+        assert_eq!(parse(&["--ext", "sidebar"]), Err(CommandLineError::UnknownExtension("sidebar".to_string())));
+    }
+
+    #[test]
+    fn unknown_arguments_and_everything_after_a_double_dash_go_to_neovim() {
+        let settings = settings(&["file.txt", "-u", "NONE", "--", "--geometry", "1x1", "--"]);
+        assert_eq!(settings.neovim_args, vec!["file.txt", "-u", "NONE", "--geometry", "1x1", "--"]);
+        assert_eq!(settings.geometry, None);
+    }
+
+    #[test]
+    fn flags_win_over_the_config_file() {
+        let config = Config { maximized: Some(true), geometry: Some("80x20".to_string()), ..Config::default() };
+        let settings = settings(&["--no-maximized"]).with_config(config.clone(), None);
+        assert!(!settings.maximized());
+        assert_eq!(settings.initial_dimensions(), (80, 20));
+
+        let settings = CommandLineSettings::default().with_config(config, None);
+        assert!(settings.maximized());
+    }
+
+    #[test]
+    fn neovim_bin_variable_beats_the_config_file_but_not_the_flag() {
+        let config = Config { neovim_bin: Some("/config/nvim".to_string()), ..Config::default() };
+        let variable = || Some("/variable/nvim".to_string());
+
+        let binary = |args: &[&str], config: &Config, variable: Option<String>|
+            settings(args).with_config(config.clone(), variable).neovim_binary();
+        assert_eq!(binary(&["--neovim-bin", "/flag/nvim"], &config, variable()), "/flag/nvim");
+        assert_eq!(binary(&[], &config, variable()), "/variable/nvim");
+        assert_eq!(binary(&[], &config, None), "/config/nvim");
+        assert_eq!(binary(&[], &Config::default(), None), "nvim");
+    }
+
+    #[test]
+    fn server_takes_an_address_or_falls_back_to_nvim_listen_address() {
+        assert_eq!(settings(&["--server", "127.0.0.1:6666"]).server, Some("127.0.0.1:6666".to_string()));
+        assert_eq!(settings(&["--server=/tmp/nvim.sock"]).server, Some("/tmp/nvim.sock".to_string()));
+
+        assert_eq!(parse(&["--server"]), Err(CommandLineError::MissingValue("--server".to_string())));
+        assert_eq!(parse(&["--server", "--multigrid"]), Err(CommandLineError::MissingValue("--server".to_string())));
+
+        let listen_address = || Some("/tmp/listen.sock".to_string());
+        let server = |args: &[&str]| match parse_args(args.iter().map(|arg| arg.to_string()), listen_address()) {
+            Ok(CommandLineRequest::Run(settings)) => (settings.server, settings.ui_extensions),
+            other => panic!("expected settings but got {:?}", other)
+        };
+        assert_eq!(server(&["--server"]), (listen_address(), vec![]));
+        assert_eq!(server(&["--server", "--multigrid"]), (listen_address(), vec!["multigrid".to_string()]));
+        assert_eq!(server(&["--server", "127.0.0.1:6666"]), (Some("127.0.0.1:6666".to_string()), vec![]));
+    }
+
+    #[test]
+    fn wrappers_are_split_like_a_shell_would() {
+        assert_eq!(split_command("ssh -t 'my host' \"a b\" c\\ d"), vec!["ssh", "-t", "my host", "a b", "c d"]);
+        assert_eq!(split_command("  "), Vec::<String>::new());
+    }
+}
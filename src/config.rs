@@ -0,0 +1,132 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::command_line::{parse_geometry, CommandLineSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RendererBackend {
+    Integrated,
+    Discrete
+}
+
+impl Default for RendererBackend {
+    fn default() -> RendererBackend {
+        RendererBackend::Integrated
+    }
+}
+
+impl RendererBackend {
+    pub fn parse(value: &str) -> Option<RendererBackend> {
+        match value {
+            "integrated" => Some(RendererBackend::Integrated),
+            "discrete" => Some(RendererBackend::Discrete),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontConfig {
+    #[serde(default)]
+    pub fallbacks: Vec<String>
+}
+
+// The preferences which have to be known before neovim starts. Every field is optional, and the
+// command line wins over anything set here.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub neovim_bin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wrapper: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximized: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renderer_backend: Option<RendererBackend>,
+    #[serde(default)]
+    pub font: FontConfig
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, error) => write!(f, "could not read config file {}: {}", path.display(), error),
+            ConfigError::Parse(path, error) => write!(f, "invalid config file {}: {}", path.display(), error),
+            ConfigError::Invalid(path, message) => write!(f, "invalid config file {}: {}", path.display(), message)
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/neovide/config.toml, falling back to the platform's config directory
+pub fn default_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::config_dir)
+        .map(|directory| directory.join("neovide").join("config.toml"))
+}
+
+impl Config {
+    pub fn parse(contents: &str, path: &Path) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(contents)
+            .map_err(|error| ConfigError::Parse(path.to_path_buf(), error))?;
+
+        if let Some(geometry) = &config.geometry {
+            parse_geometry(geometry)
+                .map_err(|error| ConfigError::Invalid(path.to_path_buf(), error.to_string()))?;
+        }
+        if config.wrapper.as_ref().map(Vec::is_empty).unwrap_or(false) {
+            return Err(ConfigError::Invalid(path.to_path_buf(), "wrapper must not be empty".to_string()));
+        }
+
+        Ok(config)
+    }
+
+    // A missing file is only an error when it was asked for explicitly with --config
+    pub fn load(explicit_path: Option<&Path>) -> Result<Config, ConfigError> {
+        let path = match explicit_path.map(Path::to_path_buf).or_else(default_config_path) {
+            Some(path) => path,
+            None => return Ok(Config::default())
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents, &path),
+            Err(error) if error.kind() == io::ErrorKind::NotFound && explicit_path.is_none() => Ok(Config::default()),
+            Err(error) => Err(ConfigError::Read(path, error))
+        }
+    }
+
+    pub fn from_settings(settings: &CommandLineSettings) -> Config {
+        let (columns, rows) = settings.initial_dimensions();
+        Config {
+            geometry: Some(format!("{}x{}", columns, rows)),
+            neovim_bin: Some(settings.neovim_binary()),
+            wrapper: Some(settings.wrapper.clone()).filter(|wrapper| !wrapper.is_empty()),
+            maximized: Some(settings.maximized()),
+            renderer_backend: Some(settings.renderer_backend()),
+            font: FontConfig {
+                fallbacks: settings.font_fallbacks.clone()
+            }
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Could not serialize the configuration")
+    }
+}
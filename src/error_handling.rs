@@ -1,12 +1,15 @@
+#[cfg(not(target_os = "linux"))]
 use msgbox::IconType;
 
+#[cfg(target_os = "linux")]
 fn show_error(title: &str, explanation: &str) -> ! {
-    if cfg!(target_os = "linux") {
-        panic!("{}: {}", title, explanation);
-    } else {
-        msgbox::create(title, explanation, IconType::Error);
-        panic!(explanation.to_string());
-    }
+    panic!("{}: {}", title, explanation);
+}
+
+#[cfg(not(target_os = "linux"))]
+fn show_error(title: &str, explanation: &str) -> ! {
+    msgbox::create(title, explanation, IconType::Error);
+    panic!(explanation.to_string());
 }
 
 pub trait ResultPanicExplanation<T, E: ToString> {
@@ -0,0 +1,61 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use neovide_core::bridge::apply_recording;
+use neovide_core::editor::diff_dumps;
+use neovide_core::session::SessionContext;
+
+use crate::command_line::COMMAND_LINE_SETTINGS;
+
+pub enum GridCheck {
+    Matched,
+    Updated,
+    Mismatched(String)
+}
+
+// Replays a recording into the editor and compares the resulting state with the dump stored in
+// expected_path. With update set the dump is written instead. A missing dump is an error so that
+// a check can't pass just because its dump was never committed.
+pub fn check_grid(recording: &Path, expected_path: &Path, update: bool) -> Result<GridCheck, String> {
+    let context = SessionContext::new(COMMAND_LINE_SETTINGS.session_options());
+    apply_recording(&context, recording, |_| Ok(()))?;
+    let actual = context.editor.lock().unwrap().dump_state();
+
+    if update {
+        fs::write(expected_path, &actual)
+            .map_err(|error| format!("Could not write {}: {}", expected_path.display(), error))?;
+        return Ok(GridCheck::Updated);
+    }
+
+    let expected = fs::read_to_string(expected_path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => format!("{} does not exist, run with --update-grid to create it", expected_path.display()),
+        _ => format!("Could not read {}: {}", expected_path.display(), error)
+    })?;
+
+    match diff_dumps(&expected, &actual) {
+        Some(diff) => Ok(GridCheck::Mismatched(diff)),
+        None => Ok(GridCheck::Matched)
+    }
+}
+
+pub fn run(recording: &Path, expected_path: &Path, update: bool) -> i32 {
+    match check_grid(recording, expected_path, update) {
+        Ok(GridCheck::Matched) => {
+            println!("{} matches {}", recording.display(), expected_path.display());
+            0
+        },
+        Ok(GridCheck::Updated) => {
+            println!("Wrote {}", expected_path.display());
+            0
+        },
+        Ok(GridCheck::Mismatched(diff)) => {
+            println!("{} does not match {}:\n{}", recording.display(), expected_path.display(), diff);
+            1
+        },
+        Err(message) => {
+            eprintln!("error: {}", message);
+            2
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+
+use skulpin::skia_safe::{icu, EncodedImageFormat, Surface};
+
+use neovide_core::bridge::apply_recording;
+use neovide_core::session::SessionContext;
+
+use crate::command_line::COMMAND_LINE_SETTINGS;
+use crate::renderer::Renderer;
+
+fn frame_size(renderer: &Renderer, context: &SessionContext) -> (i32, i32) {
+    let (columns, rows) = context.editor.lock().unwrap().size;
+    let padding = context.settings().padding as f32 * 2.0;
+    (
+        (columns as f32 * renderer.font_width + padding).ceil().max(1.0) as i32,
+        (rows as f32 * renderer.font_height + padding).ceil().max(1.0) as i32
+    )
+}
+
+fn write_snapshot(renderer: &mut Renderer, context: &SessionContext, surface: &mut Option<Surface>, path: &Path) -> Result<(), String> {
+    // The first draw after a font or padding change only measures, so draw until the size settles
+    loop {
+        let (width, height) = frame_size(renderer, context);
+        if surface.as_ref().map(|surface| (surface.width(), surface.height())) != Some((width, height)) {
+            *surface = Some(Surface::new_raster_n32_premul((width, height))
+                .ok_or_else(|| format!("Could not create a {}x{} surface", width, height))?);
+        }
+
+        let canvas = surface.as_mut().unwrap().canvas();
+        if !renderer.draw_frame(canvas, (width as f32, height as f32), &|_| {}, None) {
+            break;
+        }
+    }
+
+    let png = surface.as_mut().unwrap()
+        .image_snapshot()
+        .encode_to_data(EncodedImageFormat::PNG)
+        .ok_or_else(|| format!("Could not encode {}", path.display()))?;
+    fs::write(path, png.as_bytes())
+        .map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+// Renders a recording onto a cpu raster surface with no window or gpu, writing final.png and,
+// when every_flush is set, frame-NNNNN.png for each flushed batch.
+pub fn run(snapshot_directory: &Path, recording: &Path, every_flush: bool) -> Result<(), String> {
+    icu::init();
+    fs::create_dir_all(snapshot_directory)
+        .map_err(|error| format!("Could not create {}: {}", snapshot_directory.display(), error))?;
+
+    let context = SessionContext::new(COMMAND_LINE_SETTINGS.session_options());
+    // Snapshots have to show where the cursor is, not where an animation has got it to so far
+    context.settings.lock().unwrap().cursor_animation = false;
+
+    let mut renderer = Renderer::new(context.clone());
+    let mut surface = None;
+
+    apply_recording(&context, recording, |flush_index| {
+        if every_flush {
+            let path = snapshot_directory.join(format!("frame-{:05}.png", flush_index));
+            write_snapshot(&mut renderer, &context, &mut surface, &path)
+        } else {
+            Ok(())
+        }
+    })?;
+
+    write_snapshot(&mut renderer, &context, &mut surface, &snapshot_directory.join("final.png"))
+}
@@ -0,0 +1,41 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::{Log, LevelFilter, Metadata, Record};
+
+struct FileLogger {
+    file: Mutex<File>
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "[{}] {}: {}", record.level(), record.target(), record.args()).ok();
+    }
+
+    fn flush(&self) {
+        self.file.lock().unwrap().flush().ok();
+    }
+}
+
+pub fn initialize_logging(log_file: Option<&Path>) {
+    if let Some(log_file) = log_file {
+        match File::create(log_file) {
+            Ok(file) => {
+                let logger = FileLogger { file: Mutex::new(file) };
+                if log::set_boxed_logger(Box::new(logger)).is_ok() {
+                    log::set_max_level(LevelFilter::Info);
+                }
+                return;
+            },
+            Err(error) => eprintln!("Could not open log file {}: {}", log_file.display(), error)
+        }
+    }
+    env_logger::init();
+}
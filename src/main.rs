@@ -1,11 +1,15 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod bridge;
-mod editor;
 mod window;
 mod renderer;
+mod keybindings;
+mod logging;
+mod command_line;
+mod headless;
+mod grid_check;
+mod config;
 mod error_handling;
-mod redraw_scheduler;
+mod system_clipboard;
 
 #[macro_use] extern crate derive_new;
 #[macro_use] extern crate rust_embed;
@@ -13,12 +17,67 @@ mod redraw_scheduler;
 
 use lazy_static::initialize;
 
-use bridge::BRIDGE;
+use neovide_core::session::Session;
+
+use command_line::COMMAND_LINE_SETTINGS;
+use logging::initialize_logging;
+use system_clipboard::SystemClipboard;
 use window::ui_loop;
 
-pub const INITIAL_DIMENSIONS: (u64, u64) = (100, 50);
+// macOS apps are launched through their bundle and Windows builds have no console to leave, so
+// only the other unixes relaunch in the background
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn detach_from_terminal() {
+    use std::process::{Command, Stdio};
+
+    // Relaunch in the background so that the shell which started neovide gets its prompt back
+    let current_exe = std::env::current_exe().expect("Could not locate the neovide executable");
+    Command::new(current_exe)
+        .arg("--nofork")
+        .args(std::env::args().skip(1))
+        .stdin(Stdio::null())
+        .spawn()
+        .expect("Could not relaunch neovide in the background");
+    std::process::exit(0);
+}
 
 fn main() {
-    initialize(&BRIDGE);
-    ui_loop();
+    initialize(&COMMAND_LINE_SETTINGS);
+
+    if let (Some(expected_grid), Some(recording)) = (&COMMAND_LINE_SETTINGS.check_grid, &COMMAND_LINE_SETTINGS.replay) {
+        initialize_logging(COMMAND_LINE_SETTINGS.log_file.as_deref());
+        std::process::exit(grid_check::run(recording, expected_grid, COMMAND_LINE_SETTINGS.update_grid));
+    }
+
+    if let (Some(snapshot_directory), Some(recording)) = (&COMMAND_LINE_SETTINGS.headless, &COMMAND_LINE_SETTINGS.replay) {
+        initialize_logging(COMMAND_LINE_SETTINGS.log_file.as_deref());
+        if let Err(message) = headless::run(snapshot_directory, recording, COMMAND_LINE_SETTINGS.snapshot_every_flush) {
+            eprintln!("error: {}", message);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Checked before detaching so that the error reaches the terminal
+    let recorder = match COMMAND_LINE_SETTINGS.recorder() {
+        Ok(recorder) => recorder,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        if !COMMAND_LINE_SETTINGS.no_fork {
+            detach_from_terminal();
+        }
+    }
+
+    initialize_logging(COMMAND_LINE_SETTINGS.log_file.as_deref());
+    let options = COMMAND_LINE_SETTINGS.session_options();
+    let clipboard = options.clipboard.create_backend()
+        .unwrap_or_else(|| Box::new(SystemClipboard::default()));
+    let session = Session::start(COMMAND_LINE_SETTINGS.connection(), options, clipboard, recorder);
+    ui_loop(session);
 }
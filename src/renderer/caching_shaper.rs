@@ -5,6 +5,8 @@ use skulpin::skia_safe::{TextBlob, Font as SkiaFont, Typeface, TextBlobBuilder,
 use font_kit::{source::SystemSource, metrics::Metrics, properties::{Properties, Weight, Style, Stretch}, family_name::FamilyName, font::Font, };
 use skribo::{LayoutSession, FontRef as SkriboFont, FontFamily, FontCollection, TextStyle};
 
+use crate::command_line::COMMAND_LINE_SETTINGS;
+
 const STANDARD_CHARACTER_STRING: &'static str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890";
 
 const MONOSPACE_FONT: &'static str = "Fira Code Regular Nerd Font Complete.otf";
@@ -48,23 +50,26 @@ fn build_collection_by_font_name(font_name: Option<&str>, bold: bool, italic: bo
 
     let mut collection = FontCollection::new();
 
-    if let Some(font_name) = font_name {
-        let weight = if bold {
-            Weight::BOLD
-        } else {
-            Weight::NORMAL
-        };
-
-        let style = if italic {
-            Style::Italic
-        } else {
-            Style::Normal
-        };
-
-        let properties = Properties {
-            weight, style, stretch: Stretch::NORMAL
-        };
-        if let Ok(custom) = source.select_best_match(&[FamilyName::Title(font_name.to_string())], &properties) {
+    let weight = if bold {
+        Weight::BOLD
+    } else {
+        Weight::NORMAL
+    };
+
+    let style = if italic {
+        Style::Italic
+    } else {
+        Style::Normal
+    };
+
+    let properties = Properties {
+        weight, style, stretch: Stretch::NORMAL
+    };
+
+    // The font from guifont comes first, then the fallbacks from config.toml in order
+    let system_fonts = font_name.into_iter().chain(COMMAND_LINE_SETTINGS.font_fallbacks.iter().map(String::as_str));
+    for system_font in system_fonts {
+        if let Ok(custom) = source.select_best_match(&[FamilyName::Title(system_font.to_string())], &properties) {
             let font = custom.load().unwrap();
             collection.add_family(FontFamily::new_from_font(font));
         }
@@ -2,11 +2,13 @@ use std::time::{Duration, Instant};
 
 use skulpin::skia_safe::{Canvas, Paint, Path, Point};
 
-use crate::renderer::CachingShaper;
-use crate::editor::{EDITOR, Colors, Cursor, CursorShape};
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
+use neovide_core::editor::{Colors, Cursor, CursorShape};
+use neovide_core::redraw_scheduler::RedrawScheduler;
+use neovide_core::session::SessionContext;
+use neovide_core::settings::Settings;
+
+use crate::renderer::{CachingShaper, ToSkiaColor};
 
-const AVERAGE_MOTION_PERCENTAGE: f32 = 0.7;
 const MOTION_PERCENTAGE_SPREAD: f32 = 0.5;
 const COMMAND_LINE_DELAY_FRAMES: u64 = 5;
 const DEFAULT_CELL_PERCENTAGE: f32 = 1.0 / 8.0;
@@ -34,7 +36,7 @@ impl BlinkStatus {
         }
     }
 
-    pub fn update_status(&mut self, new_cursor: &Cursor) -> bool {
+    pub fn update_status(&mut self, new_cursor: &Cursor, redraw_scheduler: &RedrawScheduler) -> bool {
         if self.previous_cursor.is_none() || new_cursor != self.previous_cursor.as_ref().unwrap() {
             self.previous_cursor = Some(new_cursor.clone());
             self.last_transition = Instant::now();
@@ -73,7 +75,7 @@ impl BlinkStatus {
         }).map(|delay| self.last_transition + Duration::from_millis(delay));
 
         if let Some(scheduled_frame) = scheduled_frame {
-            REDRAW_SCHEDULER.schedule(scheduled_frame);
+            redraw_scheduler.schedule(scheduled_frame);
         }
 
         match self.state {
@@ -97,11 +99,16 @@ impl Corner {
         }
     }
 
-    pub fn update(&mut self, font_dimensions: Point, destination: Point) -> bool {
+    pub fn update(&mut self, font_dimensions: Point, destination: Point, settings: &Settings) -> bool {
         let relative_scaled_position: Point = 
             (self.relative_position.x * font_dimensions.x, self.relative_position.y * font_dimensions.y).into();
         let corner_destination = destination + relative_scaled_position;
 
+        if !settings.cursor_animation {
+            self.current_position = corner_destination;
+            return false;
+        }
+
         let delta = corner_destination - self.current_position;
 
         if delta.length() > 0.0 {
@@ -112,9 +119,9 @@ impl Corner {
             let motion_scale = delta.dot(relative_scaled_position) / delta.length() / font_dimensions.length();
 
             // The motion_percentage is then equal to the motion_scale factor times the
-            // MOTION_PERCENTAGE_SPREAD and added to the AVERAGE_MOTION_PERCENTAGE. This way all of
-            // the percentages are positive and spread out by the spread constant.
-            let motion_percentage = motion_scale * MOTION_PERCENTAGE_SPREAD + AVERAGE_MOTION_PERCENTAGE;
+            // MOTION_PERCENTAGE_SPREAD and added to the cursor_motion_percentage setting. This way
+            // all of the percentages are positive and spread out by the spread constant.
+            let motion_percentage = motion_scale * MOTION_PERCENTAGE_SPREAD + settings.cursor_motion_percentage;
 
             // Then the current_position is animated by taking the delta vector, multiplying it by
             // the motion_percentage and adding the resulting value to the current position causing
@@ -175,11 +182,11 @@ impl CursorRenderer {
             cursor: Cursor, default_colors: &Colors, 
             font_width: f32, font_height: f32,
             paint: &mut Paint, shaper: &mut CachingShaper, 
-            canvas: &mut Canvas) {
-        let render = self.blink_status.update_status(&cursor);
+            context: &SessionContext, settings: &Settings, canvas: &mut Canvas) {
+        let render = self.blink_status.update_status(&cursor, &context.redraw_scheduler);
 
         self.previous_position = {
-            let editor = EDITOR.lock().unwrap();
+            let editor = context.editor.lock().unwrap();
             let (_, grid_y) = cursor.position;
             let (_, previous_y) = self.previous_position;
             let (_, height) = editor.size;
@@ -200,16 +207,15 @@ impl CursorRenderer {
         let (grid_x, grid_y) = self.previous_position;
 
         let (character, font_dimensions): (String, Point) = {
-            let editor = EDITOR.lock().unwrap();
-            let character = editor.grid
-                .get(grid_y as usize)
-                .and_then(|row| row.get(grid_x as usize).cloned())
+            // The position is on the screen by now, so the character is whatever is shown there
+            let editor = context.editor.lock().unwrap();
+            let character = editor.visible_cell((grid_x, grid_y))
+                .cloned()
                 .flatten()
                 .map(|(character, _)| character)
                 .unwrap_or(' '.to_string());
-            let is_double = editor.grid
-                .get(grid_y as usize)
-                .and_then(|row| row.get(grid_x as usize + 1).cloned())
+            let is_double = editor.visible_cell((grid_x + 1, grid_y))
+                .cloned()
                 .flatten()
                 .map(|(character, _)| character.is_empty())
                 .unwrap_or(false);
@@ -228,13 +234,13 @@ impl CursorRenderer {
         let mut animating = false;
         if !center_destination.is_zero() {
             for corner in self.corners.iter_mut() {
-                let corner_animating = corner.update(font_dimensions, center_destination);
+                let corner_animating = corner.update(font_dimensions, center_destination, settings);
                 animating = animating || corner_animating;
             }
         }
 
         if animating {
-            REDRAW_SCHEDULER.queue_next_frame();
+            context.redraw_scheduler.queue_next_frame();
         }
 
         if cursor.enabled && render {
@@ -1,5 +1,7 @@
+use std::collections::HashMap;
+
 use skulpin::CoordinateSystemHelper;
-use skulpin::skia_safe::{Canvas, Paint, Surface, Budgeted, Rect, colors};
+use skulpin::skia_safe::{Canvas, Paint, Path, Surface, Budgeted, Rect, Color as SkiaColor, Color4f, colors, MaskFilter, BlurStyle, ClipOp, BlendMode};
 use skulpin::skia_safe::gpu::SurfaceOrigin;
 use unicode_segmentation::UnicodeSegmentation;
 
@@ -8,31 +10,98 @@ mod cursor_renderer;
 
 pub use caching_shaper::CachingShaper;
 
+use neovide_core::bridge::SessionStatus;
+use neovide_core::editor::{Color, Style, Colors, FloatingWindow, GridDrawCommands};
+use neovide_core::session::SessionContext;
+use neovide_core::settings::Settings;
+
 use cursor_renderer::CursorRenderer;
-use crate::editor::{EDITOR, Style, Colors};
+
+// neovide-core has no skia dependency, so its colors are converted as they are drawn
+pub trait ToSkiaColor {
+    fn to_color(&self) -> SkiaColor;
+}
+
+impl ToSkiaColor for Color {
+    fn to_color(&self) -> SkiaColor {
+        Color4f::new(self.r, self.g, self.b, self.a).to_color()
+    }
+}
+
+// Neovim blends everything but floats into the screen itself, so only floats are drawn
+// translucent. Cells in the popup menu which don't set a blend of their own use pumblend.
+fn blended_style(style: &Option<Style>, default_colors: &Colors, floating: Option<&FloatingWindow>, popup_menu_blend: u8) -> Style {
+    let mut style = style.clone().unwrap_or_else(|| Style::new(default_colors.clone()));
+    style.blend = match floating {
+        Some(floating) if floating.popup_menu && style.blend == 0 => popup_menu_blend,
+        Some(_) => style.blend,
+        None => 0
+    };
+    style
+}
+
+// Floats over the message grid are part of the message area, so they aren't decorated
+fn decorated_float(grid: &GridDrawCommands, message_grid: Option<u64>) -> Option<&FloatingWindow> {
+    grid.floating.as_ref().filter(|floating| Some(floating.anchor_grid) != message_grid)
+}
+
+// Draws the shadow around a float and clips the canvas to the float's outline, which is rounded
+// when a corner radius is set. Floats which can't be focused, such as hover docs, get a lighter
+// shadow than the ones the user types into.
+fn draw_float_decoration(canvas: &mut Canvas, destination: &Rect, floating: &FloatingWindow, settings: &Settings) {
+    let corner_radius = settings.floating_corner_radius;
+    let mut outline = Path::new();
+    outline.add_round_rect(destination, (corner_radius, corner_radius), None);
+
+    if settings.floating_shadow_radius > 0.0 {
+        let mut shadow_color = settings.floating_shadow_color.clone();
+        if !floating.focusable {
+            shadow_color.a /= 2.0;
+        }
+
+        let mut shadow_paint = Paint::default();
+        shadow_paint.set_anti_alias(true);
+        shadow_paint.set_color(shadow_color.to_color());
+        shadow_paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, settings.floating_shadow_radius / 2.0, None));
+
+        // The shadow is left out from under the float so that it doesn't show through
+        // translucent backgrounds
+        canvas.save();
+        canvas.clip_path(&outline, ClipOp::Difference, Some(true));
+        canvas.draw_path(&outline.with_offset((settings.floating_shadow_offset_x, settings.floating_shadow_offset_y)), &shadow_paint);
+        canvas.restore();
+    }
+
+    if corner_radius > 0.0 {
+        canvas.clip_path(&outline, None, Some(true));
+    }
+}
 
 pub struct Renderer {
-    surface: Option<Surface>,
+    grid_surfaces: HashMap<u64, (Surface, (u64, u64))>,
     paint: Paint,
     shaper: CachingShaper,
 
     pub font_width: f32,
     pub font_height: f32,
+    padding: f32,
     cursor_renderer: CursorRenderer,
+    context: SessionContext
 }
 
 impl Renderer {
-    pub fn new() -> Renderer {
-        let surface = None;
+    pub fn new(context: SessionContext) -> Renderer {
+        let grid_surfaces = HashMap::new();
         let mut paint = Paint::new(colors::WHITE, None);
         paint.set_anti_alias(false);
         
         let mut shaper = CachingShaper::new();
 
         let (font_width, font_height) = shaper.font_base_dimensions();
+        context.editor.lock().unwrap().font_dimensions = (font_width, font_height);
         let cursor_renderer = CursorRenderer::new();
 
-        Renderer { surface, paint, shaper, font_width, font_height, cursor_renderer }
+        Renderer { grid_surfaces, paint, shaper, font_width, font_height, padding: 0.0, cursor_renderer, context }
     }
 
     fn set_font(&mut self, name: Option<&str>, size: Option<f32>) {
@@ -40,6 +109,7 @@ impl Renderer {
         let (font_width, font_height) = self.shaper.font_base_dimensions();
         self.font_width = font_width;
         self.font_height = font_height;
+        self.context.editor.lock().unwrap().font_dimensions = (font_width, font_height);
     }
 
     fn compute_text_region(&self, text: &str, grid_pos: (u64, u64), size: u16) -> Rect {
@@ -55,8 +125,13 @@ impl Renderer {
         let region = self.compute_text_region(text, grid_pos, size);
         let style = style.clone().unwrap_or_else(|| Style::new(default_colors.clone()));
 
-        self.paint.set_color(style.background(default_colors).to_color());
+        // Translucent backgrounds replace what was drawn before rather than building up over it
+        let mut background = style.background(default_colors);
+        background.a *= 1.0 - style.blend.min(100) as f32 / 100.0;
+        self.paint.set_color(background.to_color());
+        self.paint.set_blend_mode(BlendMode::Src);
         canvas.draw_rect(region, &self.paint);
+        self.paint.set_blend_mode(BlendMode::SrcOver);
     }
 
     fn draw_foreground(&mut self, canvas: &mut Canvas, text: &str, grid_pos: (u64, u64), size: u16, style: &Option<Style>, default_colors: &Colors) {
@@ -96,63 +171,237 @@ impl Renderer {
         canvas.restore();
     }
 
-    pub fn draw(&mut self, gpu_canvas: &mut Canvas, coordinate_system_helper: &CoordinateSystemHelper) -> bool {
-        let ((draw_commands, should_clear), default_colors, cursor, font_name, font_size) = {
-            let mut editor = EDITOR.lock().unwrap();
-            (
-                editor.build_draw_commands(), 
-                editor.default_colors.clone(), 
-                editor.cursor.clone(),
-                editor.font_name.clone(),
-                editor.font_size
-            )
+    fn draw_status_screen(&mut self, canvas: &mut Canvas, status: &SessionStatus, default_colors: &Colors, window_size: (f32, f32)) {
+        let (title, message, action) = match status {
+            SessionStatus::FailedToStart(message) => ("Could not start neovim", message, "Press Enter to try again or q to quit"),
+            SessionStatus::Crashed(message) => ("Neovim exited unexpectedly", message, "Press Enter to restart or q to quit"),
+            SessionStatus::Disconnected(message) => ("Lost connection to neovim", message, "Press Enter to reconnect or q to quit"),
+            _ => return
         };
 
+        let (window_width, window_height) = window_size;
+        let mut background = default_colors.background.clone().unwrap();
+        background.a = 0.9;
+        self.paint.set_color(background.to_color());
+        canvas.draw_rect(Rect::new(0.0, 0.0, window_width, window_height), &self.paint);
+
+        self.paint.set_color(default_colors.foreground.clone().unwrap().to_color());
+        for (row, line) in [title, message.as_str(), "", action].iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let x = self.font_width * 2.0;
+            let y = self.font_height * (row + 2) as f32;
+            for blob in self.shaper.shape_cached(line, false, false).iter() {
+                canvas.draw_text_blob(blob, (x, y), &self.paint);
+            }
+        }
+    }
+
+    pub fn draw(&mut self, gpu_canvas: &mut Canvas, coordinate_system_helper: &CoordinateSystemHelper) -> bool {
+        let window_size = coordinate_system_helper.window_logical_size();
+        let status = self.context.status();
+        self.draw_frame(
+            gpu_canvas, (window_size.width as f32, window_size.height as f32),
+            &|canvas| coordinate_system_helper.use_logical_coordinates(canvas),
+            Some(&status).filter(|status| status.has_ended()))
+    }
+
+    // Makes an offscreen surface big enough for a grid, on the gpu when root_canvas is
+    fn create_grid_surface(&self, root_canvas: &mut Canvas, window_width: f32, (width, height): (u64, u64)) -> Surface {
+        // Grid surfaces are drawn with logical coordinates, so they need the window's scale
+        let image_info = root_canvas.image_info();
+        let scale_factor = image_info.width() as f32 / window_width.max(1.0);
+        let dimensions = (
+            (width as f32 * self.font_width * scale_factor).ceil().max(1.0) as i32,
+            (height as f32 * self.font_height * scale_factor).ceil().max(1.0) as i32
+        );
+        let image_info = image_info.with_dimensions(dimensions);
+
+        match root_canvas.gpu_context() {
+            Some(mut context) => {
+                let budgeted = Budgeted::YES;
+                let surface_origin = SurfaceOrigin::TopLeft;
+                Surface::new_render_target(&mut context, budgeted, &image_info, None, surface_origin, None, None)
+            },
+            None => Surface::new_raster(&image_info, None, None)
+        }.expect("Could not create surface")
+    }
+
+    // Draws the editor onto root_canvas, which may be backed by the gpu or be a cpu raster. Each
+    // grid is drawn into a cached surface of its own, and the surfaces of the visible grids are
+    // then composited in draw order at their window positions, clipped to the screen. Returns
+    // true when the grid size in pixels changed.
+    pub fn draw_frame(
+            &mut self, root_canvas: &mut Canvas, window_size: (f32, f32),
+            use_logical_coordinates: &dyn Fn(&mut Canvas),
+            ended_status: Option<&SessionStatus>) -> bool {
+        let settings = self.context.settings();
+
+        let (font_name, font_size) = {
+            let editor = self.context.editor.lock().unwrap();
+            (editor.font_name.clone(), editor.font_size)
+        };
+        let font_size = font_size.unwrap_or(settings.font_size) * settings.scale;
         let font_changed = 
             font_name != self.shaper.font_name || 
-            font_size.map(|new_size| (new_size - self.shaper.base_size).abs() > std::f32::EPSILON).unwrap_or(false);
+            (font_size - self.shaper.base_size).abs() > std::f32::EPSILON;
         if font_changed {
-            self.set_font(font_name.as_deref(), font_size);
+            self.set_font(font_name.as_deref(), Some(font_size));
+            self.grid_surfaces.clear();
         }
 
-        if should_clear {
-            self.surface = None;
-        }
+        let padding = settings.padding as f32;
+        let padding_changed = (padding - self.padding).abs() > std::f32::EPSILON;
+        self.padding = padding;
 
-        let mut surface = self.surface.take().unwrap_or_else(|| {
-            let mut context = gpu_canvas.gpu_context().unwrap();
-            let budgeted = Budgeted::YES;
-            let image_info = gpu_canvas.image_info();
-            let surface_origin = SurfaceOrigin::TopLeft;
-            let mut surface = Surface::new_render_target(&mut context, budgeted, &image_info, None, surface_origin, None, None).expect("Could not create surface");
-            let canvas = surface.canvas();
-            canvas.clear(default_colors.background.clone().unwrap().to_color());
-            surface
-        });
-
-        let mut canvas = surface.canvas();
-        coordinate_system_helper.use_logical_coordinates(&mut canvas);
-
-        for command in draw_commands.iter() {
-            self.draw_background(&mut canvas, &command.text, command.grid_position.clone(), command.scale, &command.style, &default_colors);
-        }
-        for command in draw_commands.iter() {
-            self.draw_foreground(&mut canvas, &command.text, command.grid_position.clone(), command.scale, &command.style, &default_colors);
+        let (grids, default_colors, cursor, screen_size, message_grid, popup_menu_blend) = {
+            let mut editor = self.context.editor.lock().unwrap();
+
+            // Grids of destroyed windows lose their surfaces, and grids without an up to date
+            // surface are drawn again from scratch
+            let editor_grids = &editor.grids;
+            self.grid_surfaces.retain(|grid_id, _| editor_grids.contains_key(grid_id));
+            for (grid_id, grid) in editor.grids.iter_mut() {
+                let surface_matches = self.grid_surfaces.get(grid_id)
+                    .map(|(_, size)| *size == grid.size())
+                    .unwrap_or(false);
+                if !surface_matches {
+                    grid.invalidate();
+                }
+            }
+
+            let mut cursor = editor.cursor.clone();
+            cursor.position = editor.cursor_screen_position();
+            (editor.build_draw_commands(), editor.default_colors.clone(), cursor, editor.size, editor.message_grid, editor.popup_menu_blend)
+        };
+
+        let (window_width, _) = window_size;
+        for grid in grids.iter() {
+            let mut surface = match self.grid_surfaces.remove(&grid.grid) {
+                Some((surface, size)) if size == grid.size && !grid.should_clear => surface,
+                _ => self.create_grid_surface(root_canvas, window_width, grid.size)
+            };
+
+            let mut canvas = surface.canvas();
+            use_logical_coordinates(&mut canvas);
+            // Floats start out transparent so that blended backgrounds show what is beneath them
+            if grid.should_clear {
+                let clear_color = match grid.floating {
+                    Some(_) => SkiaColor::TRANSPARENT,
+                    None => default_colors.background.clone().unwrap().to_color()
+                };
+                canvas.clear(clear_color);
+            }
+
+            for command in grid.commands.iter() {
+                let style = blended_style(&command.style, &default_colors, grid.floating.as_ref(), popup_menu_blend);
+                self.draw_background(&mut canvas, &command.text, command.grid_position.clone(), command.scale, &Some(style), &default_colors);
+            }
+            for command in grid.commands.iter() {
+                self.draw_foreground(&mut canvas, &command.text, command.grid_position.clone(), command.scale, &command.style, &default_colors);
+            }
+
+            self.grid_surfaces.insert(grid.grid, (surface, grid.size));
         }
 
-        let image = surface.image_snapshot();
-        let window_size = coordinate_system_helper.window_logical_size();
-        let image_destination = Rect::new(0.0, 0.0, window_size.width as f32, window_size.height as f32);
-        gpu_canvas.draw_image_rect(image, None, &image_destination, &self.paint);
+        root_canvas.clear(default_colors.background.clone().unwrap().to_color());
+        let image_paint = Paint::default();
+        // Floats are kept on screen, but the message grid runs past the bottom of it
+        let (screen_width, screen_height) = screen_size;
+        root_canvas.save();
+        root_canvas.clip_rect(
+            Rect::from_xywh(padding, padding, screen_width as f32 * self.font_width, screen_height as f32 * self.font_height),
+            None, Some(false));
+        for grid in grids.iter().filter(|grid| grid.visible) {
+            let (column, row) = grid.position;
+            let (width, height) = grid.size;
+            let image_destination = Rect::from_xywh(
+                padding + column as f32 * self.font_width,
+                padding + row as f32 * self.font_height,
+                width as f32 * self.font_width,
+                height as f32 * self.font_height);
 
-        self.surface = Some(surface);
+            root_canvas.save();
+            if let Some(floating) = decorated_float(grid, message_grid) {
+                draw_float_decoration(root_canvas, &image_destination, floating, &settings);
+            }
 
+            let (surface, _) = self.grid_surfaces.get_mut(&grid.grid).unwrap();
+            root_canvas.draw_image_rect(surface.image_snapshot(), None, &image_destination, &image_paint);
+            root_canvas.restore();
+        }
+        root_canvas.restore();
+
+        root_canvas.save();
+        root_canvas.translate((padding, padding));
         self.cursor_renderer.draw(
             cursor, &default_colors, 
             self.font_width, self.font_height, 
             &mut self.paint, &mut self.shaper,
-            gpu_canvas);
+            &self.context, &settings, root_canvas);
+        root_canvas.restore();
+
+        if let Some(status) = ended_status {
+            self.draw_status_screen(root_canvas, status, &default_colors, window_size);
+        }
+
+        font_changed || padding_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_colors() -> Colors {
+        Colors::new(Some(Color::WHITE), Some(Color::BLACK), Some(Color::GREY))
+    }
+
+    fn style_with_blend(blend: u8) -> Option<Style> {
+        let mut style = Style::new(default_colors());
+        style.blend = blend;
+        Some(style)
+    }
+
+    fn float(anchor_grid: u64, popup_menu: bool) -> FloatingWindow {
+        FloatingWindow { order: 1, anchor_grid, focusable: true, popup_menu }
+    }
+
+    fn grid_commands(floating: Option<FloatingWindow>) -> GridDrawCommands {
+        GridDrawCommands {
+            grid: 4, size: (4, 2), position: (0, 0), visible: true,
+            floating, commands: Vec::new(), should_clear: false
+        }
+    }
+
+    #[test]
+    fn popup_menu_falls_back_to_pumblend() {
+        let popup_menu = float(1, true);
+        assert_eq!(blended_style(&style_with_blend(0), &default_colors(), Some(&popup_menu), 30).blend, 30);
+        assert_eq!(blended_style(&None, &default_colors(), Some(&popup_menu), 30).blend, 30);
+        assert_eq!(blended_style(&style_with_blend(10), &default_colors(), Some(&popup_menu), 30).blend, 10);
+    }
+
+    #[test]
+    fn other_floats_only_use_their_own_blend() {
+        let float = float(1, false);
+        assert_eq!(blended_style(&style_with_blend(0), &default_colors(), Some(&float), 30).blend, 0);
+        assert_eq!(blended_style(&style_with_blend(20), &default_colors(), Some(&float), 30).blend, 20);
+    }
+
+    // Neovim has already blended everything that isn't a float into the cells it sends
+    #[test]
+    fn grids_which_are_not_floats_are_opaque() {
+        assert_eq!(blended_style(&style_with_blend(50), &default_colors(), None, 30).blend, 0);
+        assert_eq!(blended_style(&None, &default_colors(), None, 30).blend, 0);
+    }
 
-        font_changed
+    #[test]
+    fn floats_over_the_message_grid_are_not_decorated() {
+        assert!(decorated_float(&grid_commands(Some(float(5, false))), Some(5)).is_none());
+        assert!(decorated_float(&grid_commands(Some(float(1, false))), Some(5)).is_some());
+        assert!(decorated_float(&grid_commands(Some(float(1, false))), None).is_some());
+        assert!(decorated_float(&grid_commands(None), Some(5)).is_none());
     }
 }
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+use clipboard::{ClipboardContext, ClipboardProvider};
+
+use neovide_core::clipboard::{ClipboardBackend, ClipboardContents, ClipboardError, ClipboardRegister};
+
+// The platform clipboard only holds text, so the register type of the last copy is remembered and
+// handed back for as long as the clipboard still holds that same text.
+#[derive(Default)]
+pub struct SystemClipboard {
+    last_copied: HashMap<ClipboardRegister, ClipboardContents>
+}
+
+impl SystemClipboard {
+    fn context() -> Result<ClipboardContext, ClipboardError> {
+        ClipboardProvider::new().map_err(|error| ClipboardError(error.to_string()))
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn get(&mut self, register: ClipboardRegister) -> Result<ClipboardContents, ClipboardError> {
+        let text = SystemClipboard::context()?
+            .get_contents()
+            .map_err(|error| ClipboardError(error.to_string()))?;
+
+        match self.last_copied.get(&register) {
+            Some(contents) if contents.text() == text => Ok(contents.clone()),
+            _ => Ok(ClipboardContents::from_text(&text))
+        }
+    }
+
+    fn set(&mut self, register: ClipboardRegister, contents: ClipboardContents) -> Result<(), ClipboardError> {
+        SystemClipboard::context()?
+            .set_contents(contents.text())
+            .map_err(|error| ClipboardError(error.to_string()))?;
+        self.last_copied.insert(register, contents);
+        Ok(())
+    }
+}
@@ -1,38 +1,74 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use log::error;
 use image::{load_from_memory, GenericImageView, Pixel};
 use skulpin::{CoordinateSystem, RendererBuilder, PresentMode};
 use skulpin::skia_safe::icu;
 use skulpin::winit::dpi::{LogicalSize, LogicalPosition};
-use skulpin::winit::event::{ElementState, Event, MouseScrollDelta, StartCause, WindowEvent};
+use skulpin::winit::event::{ElementState, Event, KeyboardInput, MouseScrollDelta, StartCause, VirtualKeyCode, WindowEvent};
 use skulpin::winit::event_loop::{ControlFlow, EventLoop};
-use skulpin::winit::window::{Icon, WindowBuilder};
+use skulpin::winit::window::{Fullscreen, Icon, WindowBuilder};
 
-use crate::bridge::{construct_keybinding_string, BRIDGE, UiCommand};
+use neovide_core::bridge::{Bridge, UiCommand};
+use neovide_core::clipboard::{ClipboardBackend, ClipboardRegister};
+use neovide_core::session::Session;
+
+use crate::error_handling::ResultPanicExplanation;
+use crate::keybindings::construct_keybinding_string;
 use crate::renderer::Renderer;
-use crate::redraw_scheduler::REDRAW_SCHEDULER;
-use crate::editor::EDITOR;
-use crate::INITIAL_DIMENSIONS;
+use crate::command_line::COMMAND_LINE_SETTINGS;
+use crate::config::RendererBackend;
+use crate::system_clipboard::SystemClipboard;
 
 #[derive(RustEmbed)]
 #[folder = "assets/"]
 struct Asset;
 
-fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer) {
-    if new_size.width > 0.0 && new_size.height > 0.0 {
-        let new_width = ((new_size.width + 1.0) as f32 / renderer.font_width) as u64;
-        let new_height = ((new_size.height + 1.0) as f32 / renderer.font_height) as u64;
+fn queue_command(bridge: &Bridge, command: UiCommand) {
+    bridge.queue_command(command)
+        .unwrap_or_explained_panic(
+            "Could Not Send UI Command", 
+            "Could not send UI command from the window system to the neovim process.");
+}
+
+fn handle_new_grid_size(new_size: LogicalSize, renderer: &Renderer, session: &Session) {
+    let padding = session.context.settings().padding as f64 * 2.0;
+    if new_size.width > padding && new_size.height > padding {
+        let new_width = ((new_size.width - padding + 1.0) as f32 / renderer.font_width) as u64;
+        let new_height = ((new_size.height - padding + 1.0) as f32 / renderer.font_height) as u64;
         // Add 1 here to make sure resizing doesn't change the grid size on startup
-        BRIDGE.queue_command(UiCommand::Resize { width: new_width as i64, height: new_height as i64 });
+        queue_command(&session.bridge, UiCommand::Resize { width: new_width as i64, height: new_height as i64 });
+    }
+}
+
+fn handle_status_screen_input(input: KeyboardInput, bridge: &Bridge, control_flow: &mut ControlFlow) {
+    if let KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(keycode), .. } = input {
+        match keycode {
+            VirtualKeyCode::Return => queue_command(bridge, UiCommand::Restart),
+            VirtualKeyCode::Q | VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
+            _ => {}
+        }
     }
 }
 
-pub fn ui_loop() {
+// The paste shortcut always reads the system clipboard, whichever backend --clipboard offers neovim
+fn handle_keybinding(keybinding: String, session: &Session) {
+    if Some(keybinding.as_str()) == COMMAND_LINE_SETTINGS.paste_keybinding() {
+        match SystemClipboard::default().get(ClipboardRegister::Clipboard) {
+            Ok(contents) => queue_command(&session.bridge, UiCommand::Paste(contents.text())),
+            Err(error) => error!("Could not paste: {}", error)
+        }
+    } else {
+        queue_command(&session.bridge, UiCommand::Keyboard(keybinding));
+    }
+}
+
+pub fn ui_loop(session: Session) {
     let event_loop = EventLoop::<()>::with_user_event();
 
-    let mut renderer = Renderer::new();
-    let (width, height) = INITIAL_DIMENSIONS;
+    let mut renderer = Renderer::new(session.context.clone());
+    let (width, height) = COMMAND_LINE_SETTINGS.initial_dimensions();
     let logical_size = LogicalSize::new(
         (width as f32 * renderer.font_width) as f64, 
         (height as f32 * renderer.font_height + 1.0) as f64
@@ -54,11 +90,15 @@ pub fn ui_loop() {
         .with_title(&title)
         .with_inner_size(logical_size)
         .with_window_icon(Some(icon))
+        .with_maximized(COMMAND_LINE_SETTINGS.maximized())
         .build(&event_loop)
         .expect("Failed to create window"));
 
-    let mut skulpin_renderer = RendererBuilder::new()
-        .prefer_integrated_gpu()
+    let renderer_builder = match COMMAND_LINE_SETTINGS.renderer_backend() {
+        RendererBackend::Integrated => RendererBuilder::new().prefer_integrated_gpu(),
+        RendererBackend::Discrete => RendererBuilder::new().prefer_discrete_gpu()
+    };
+    let mut skulpin_renderer = renderer_builder
         .use_vulkan_debug_layer(true)
         .present_mode_priority(vec![PresentMode::Mailbox, PresentMode::Immediate])
         .coordinate_system(CoordinateSystem::Logical)
@@ -69,6 +109,9 @@ pub fn ui_loop() {
 
     let mut mouse_down = false;
     let mut mouse_pos = (0, 0);
+    // Drags keep going to the grid the button was pressed on, even once the mouse leaves it
+    let mut drag_grid = None;
+    let mut fullscreen = false;
 
     event_loop.run(move |event, _window_target, control_flow| {
         match event {
@@ -80,13 +123,19 @@ pub fn ui_loop() {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => {
+                if session.bridge.connection.is_remote() && session.context.status().is_running() {
+                    queue_command(&session.bridge, UiCommand::Detach);
+                } else {
+                    *control_flow = ControlFlow::Exit;
+                }
+            },
 
             Event::WindowEvent {
                 event: WindowEvent::Resized(new_size),
                 ..
             } => {
-                handle_new_grid_size(new_size, &renderer)
+                handle_new_grid_size(new_size, &renderer, &session)
             },
 
             Event::WindowEvent {
@@ -96,9 +145,20 @@ pub fn ui_loop() {
                 },
                 ..
             } => {
-                construct_keybinding_string(input)
-                    .map(UiCommand::Keyboard)
-                    .map(|keybinding_string| BRIDGE.queue_command(keybinding_string));
+                if session.context.status().has_ended() {
+                    handle_status_screen_input(input, &session.bridge, control_flow);
+                } else {
+                    if let Some(keybinding) = construct_keybinding_string(input) {
+                        handle_keybinding(keybinding, &session);
+                    }
+                }
+            },
+
+            Event::WindowEvent {
+                event: WindowEvent::DroppedFile(path),
+                ..
+            } => {
+                queue_command(&session.bridge, UiCommand::Paste(path.to_string_lossy().into_owned()));
             },
 
             Event::WindowEvent {
@@ -109,12 +169,14 @@ pub fn ui_loop() {
                 ..
             } => {
                 let position: LogicalPosition = position;
-                let grid_y = (position.x / renderer.font_width as f64) as i64;
-                let grid_x = (position.y / renderer.font_height as f64) as i64;
+                let padding = session.context.settings().padding as f64;
+                let grid_y = ((position.x - padding).max(0.0) / renderer.font_width as f64) as i64;
+                let grid_x = ((position.y - padding).max(0.0) / renderer.font_height as f64) as i64;
                 let (old_x, old_y) = mouse_pos;
                 mouse_pos = (grid_x, grid_y);
                 if mouse_down && (old_x != grid_x || old_y != grid_y) {
-                    BRIDGE.queue_command(UiCommand::Drag(grid_x, grid_y));
+                    let (grid, position) = session.context.mouse_target(mouse_pos, drag_grid);
+                    queue_command(&session.bridge, UiCommand::Drag { grid, position });
                 }
             }
 
@@ -138,8 +200,9 @@ pub fn ui_loop() {
                 };
 
                 if let Some(input_type) = input_type {
-                    let (grid_x, grid_y) = mouse_pos;
-                    BRIDGE.queue_command(UiCommand::MouseButton { action: input_type.to_string(), position: (grid_x, grid_y) });
+                    let (grid, position) = session.context.mouse_target(mouse_pos, drag_grid);
+                    drag_grid = if mouse_down { Some(grid) } else { None };
+                    queue_command(&session.bridge, UiCommand::MouseButton { action: input_type.to_string(), grid, position });
                 }
             }
 
@@ -159,7 +222,8 @@ pub fn ui_loop() {
                 };
 
                 if let Some(input_type) = vertical_input_type {
-                    BRIDGE.queue_command(UiCommand::Scroll { direction: input_type.to_string(), position: mouse_pos });
+                    let (grid, position) = session.context.mouse_target(mouse_pos, None);
+                    queue_command(&session.bridge, UiCommand::Scroll { direction: input_type.to_string(), grid, position });
                 }
 
                 let horizontal_input_type = if horizontal > 0.0 {
@@ -171,23 +235,40 @@ pub fn ui_loop() {
                 };
 
                 if let Some(input_type) = horizontal_input_type {
-                    BRIDGE.queue_command(UiCommand::Scroll { direction: input_type.to_string(), position: mouse_pos });
+                    let (grid, position) = session.context.mouse_target(mouse_pos, None);
+                    queue_command(&session.bridge, UiCommand::Scroll { direction: input_type.to_string(), grid, position });
                 }
             }
 
             Event::RedrawRequested { .. } => {
+                // The session has nothing left to show once neovim quits or the ui detaches
+                if session.context.status().has_exited() {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+
                 let frame_start = Instant::now();
 
-                let editor_title = { EDITOR.lock().unwrap().title.clone() };
+                let editor_title = { session.context.editor.lock().unwrap().title.clone() };
                 if title != editor_title {
                     title = editor_title;
                     window.set_title(&title);
                 }
 
-                if REDRAW_SCHEDULER.should_draw() {
+                let settings = session.context.settings();
+                if fullscreen != settings.fullscreen {
+                    fullscreen = settings.fullscreen;
+                    if fullscreen {
+                        window.set_fullscreen(Some(Fullscreen::Borderless(window.current_monitor())));
+                    } else {
+                        window.set_fullscreen(None);
+                    }
+                }
+
+                if session.context.redraw_scheduler.should_draw() {
                     if let Err(_)  = skulpin_renderer.draw(&window, |canvas, coordinate_system_helper| {
                         if renderer.draw(canvas, coordinate_system_helper) {
-                            handle_new_grid_size(window.inner_size(), &renderer)
+                            handle_new_grid_size(window.inner_size(), &renderer, &session)
                         }
                     }) {
                         println!("Render failed. Closing");
@@ -196,7 +277,7 @@ pub fn ui_loop() {
                     }
                 }
 
-                *control_flow = ControlFlow::WaitUntil(frame_start + Duration::from_secs_f32(1.0 / 60.0));
+                *control_flow = ControlFlow::WaitUntil(frame_start + Duration::from_secs_f32(1.0 / settings.refresh_rate as f32));
             },
 
             _ => {}